@@ -1,14 +1,30 @@
-use std::collections::HashSet;
+use std::collections::{HashSet, HashMap, VecDeque};
+use std::cmp::Ordering;
 
 use rand::{Rng, SeedableRng};
+use rand::seq::SliceRandom;
 use rand_chacha::ChaCha12Rng;
 
-//use ndarray::{Array, Array3};
+use ndarray::Array3;
 
-use crate::datagrid::{Selection, Room};
+use crate::datagrid::{Selection, PosList, Room, RoomEdge, in_grid};
+use crate::resource::{RoomShape, DistanceMetric};
 
 pub mod pathcarver;
 pub mod cellular_automata;
+pub mod walkers;
+pub mod erosion;
+pub mod fluid;
+pub mod rivers;
+pub mod classify;
+pub mod orientation;
+pub mod progression;
+pub mod distance;
+pub mod reachability;
+pub mod transform;
+pub mod symmetry;
+pub mod prefab;
+pub mod voxelize;
 
 #[derive(Clone)]
 pub struct RectPrism {
@@ -16,17 +32,73 @@ pub struct RectPrism {
     pub max: (usize, usize, usize)
 }
 
+// Entry for a BinaryHeap that pops the lowest cost first.
+pub struct HeapEntry<T> {
+    pub cost: f64,
+    pub item: T,
+}
+
+impl<T> PartialEq for HeapEntry<T> {
+    fn eq( &self, other: &Self ) -> bool {
+        self.cost.total_cmp( &other.cost ) == Ordering::Equal
+    }
+}
+
+impl<T> Eq for HeapEntry<T> {}
+
+impl<T> PartialOrd for HeapEntry<T> {
+    fn partial_cmp( &self, other: &Self ) -> Option<Ordering> {
+        Some( self.cmp( other ) )
+    }
+}
+
+impl<T> Ord for HeapEntry<T> {
+    fn cmp( &self, other: &Self ) -> Ordering {
+        other.cost.total_cmp( &self.cost )
+    }
+}
+
+pub struct Door {
+    pub pos: (i64, i64, i64),
+    pub orientation: i64,
+    pub room: usize,
+}
+
 pub struct AlgorithmHelper {}
 
 impl AlgorithmHelper {
-    pub fn random_rooms( count: i64, seed: i64, within: RectPrism, sized: RectPrism, allow_overlap: bool ) -> Result<( Vec<Room>, Selection), String> {
+    pub fn random_rooms( count: i64, seed: i64, within: RectPrism, sized: RectPrism, allow_overlap: bool, shape: RoomShape, margin: i64, stamp: Option<&Selection> ) -> Result<( Vec<Room>, Selection), String> {
         let mut rms = Vec::<Room>::new();
         let mut uni = Box::new( HashSet::<(i64, i64, i64)>::new() );
         let mut random = ChaCha12Rng::seed_from_u64( seed as u64 );
 
+        // stamps are moved so their bounding box starts at the origin, and always keep their own size
+        let mut stamp_cells = Vec::<(i64, i64, i64)>::new();
+        let mut sized = sized;
+        if shape == RoomShape::Stamp {
+            let Some(stamp) = stamp else { return Err( "Stamp room shape needs a stamp selection".to_string() ) };
+            if stamp.is_empty() {
+                return Err( "Stamp selection for rooms is empty".to_string() );
+            }
+            let mn = ( stamp.iter().map( |p| p.0 ).min().unwrap(), stamp.iter().map( |p| p.1 ).min().unwrap(), stamp.iter().map( |p| p.2 ).min().unwrap() );
+            let mx = ( stamp.iter().map( |p| p.0 ).max().unwrap(), stamp.iter().map( |p| p.1 ).max().unwrap(), stamp.iter().map( |p| p.2 ).max().unwrap() );
+            stamp_cells = Vec::from_iter( stamp.iter().map( |p| ( p.0 - mn.0, p.1 - mn.1, p.2 - mn.2 ) ) );
+            let ssize = ( ( mx.0 - mn.0 + 1 ) as usize, ( mx.1 - mn.1 + 1 ) as usize, ( mx.2 - mn.2 + 1 ) as usize );
+            sized = RectPrism{ min: ssize, max: ssize };
+        }
+
+        if sized.min.0 > sized.max.0 || sized.min.1 > sized.max.1 || sized.min.2 > sized.max.2 {
+            return Err( "Minimum room size is larger than maximum room size".to_string() );
+        }
+        if within.max.0 <= within.min.0 + sized.max.0 || within.max.1 <= within.min.1 + sized.max.1 || within.max.2 <= within.min.2 + sized.max.2 {
+            return Err( "Area to place rooms within is too small for the largest room size".to_string() );
+        }
+
         let mut safety = count * 2;
 
         while safety > 0 && rms.len() < count as usize {
+            safety -= 1;
+
             let sx = random.random_range( sized.min.0..=sized.max.0 );
             let sy = random.random_range( sized.min.1..=sized.max.1 );
             let sz = random.random_range( sized.min.2..=sized.max.2 );
@@ -35,26 +107,319 @@ impl AlgorithmHelper {
             let py = random.random_range( within.min.1..(within.max.1 - sy) );
             let pz = random.random_range( within.min.2..(within.max.2 - sz) );
 
-            let center = ( (px + sx / 2) as i64, py as i64,  (pz + sz / 2) as i64 );
+            let mut flip = (false, false);
+            if shape == RoomShape::LShape || shape == RoomShape::TShape {
+                flip = ( random.random_bool( 0.5 ), random.random_bool( 0.5 ) );
+            }
+
             let mut members = Box::new( HashSet::<(i64, i64, i64)>::new() );
 
-            for x in 0..sx {
-                for y in 0..sy {
-                    for z in 0..sz {
-                        members.insert( ( (x + px) as i64, (y + py) as i64, (z + pz) as i64 ) );
+            if shape == RoomShape::Stamp {
+                for cell in stamp_cells.iter() {
+                    members.insert( ( cell.0 + px as i64, cell.1 + py as i64, cell.2 + pz as i64 ) );
+                }
+            } else {
+                for x in 0..sx {
+                    for y in 0..sy {
+                        for z in 0..sz {
+                            if Self::in_room_shape( shape, (x, y, z), (sx, sy, sz), flip ) {
+                                members.insert( ( (x + px) as i64, (y + py) as i64, (z + pz) as i64 ) );
+                            }
+                        }
                     }
                 }
             }
 
-            if allow_overlap || uni.is_disjoint(&members) {
+            if members.is_empty() {
+                continue;
+            }
+
+            let free : bool;
+            if allow_overlap {
+                free = true;
+            } else if margin > 0 {
+                free = members.iter().all( |p| {
+                    for dx in -margin..=margin {
+                        for dy in -margin..=margin {
+                            for dz in -margin..=margin {
+                                if uni.contains( &( p.0 + dx, p.1 + dy, p.2 + dz ) ) {
+                                    return false;
+                                }
+                            }
+                        }
+                    }
+                    true
+                } );
+            } else {
+                free = uni.is_disjoint(&members);
+            }
+
+            if free {
+                let center = Self::floor_center( &members );
                 uni = Box::new( &*uni | &*members );
                 rms.push( Room{ members, center } );
             }
-
-            safety -= 1;
         }
 
         return Ok( (rms, uni) );
     }
+
+    // Shapes are fitted to the sx*sy*sz box; flip mirrors L and T shapes along X and Z so they can face any way.
+    fn in_room_shape( shape: RoomShape, pos: (usize, usize, usize), size: (usize, usize, usize), flip: (bool, bool) ) -> bool {
+        let x = if flip.0 { size.0 - 1 - pos.0 } else { pos.0 };
+        let y = pos.1;
+        let z = if flip.1 { size.2 - 1 - pos.2 } else { pos.2 };
+
+        // position relative to the box center, scaled to -1..1
+        let ex = ( x as f64 + 0.5 ) / size.0 as f64 * 2.0 - 1.0;
+        let ey = ( y as f64 + 0.5 ) / size.1 as f64 * 2.0 - 1.0;
+        let ez = ( z as f64 + 0.5 ) / size.2 as f64 * 2.0 - 1.0;
+
+        let mid_x = x >= size.0 / 3 && x < size.0 - size.0 / 3;
+        let mid_z = z >= size.2 / 3 && z < size.2 - size.2 / 3;
+
+        match shape {
+            RoomShape::Box | RoomShape::Stamp => true,
+            RoomShape::Ellipsoid => ex * ex + ey * ey + ez * ez <= 1.0,
+            RoomShape::Cylinder => ex * ex + ez * ez <= 1.0,
+            RoomShape::LShape => x < ( size.0 + 1 ) / 2 || z < ( size.2 + 1 ) / 2,
+            RoomShape::TShape => z < ( size.2 / 3 ).max(1) || mid_x,
+            RoomShape::Plus => mid_x || mid_z,
+        }
+    }
+
+    // The floor cell closest to the centroid of the room's lowest layer, so concave shapes still get a center inside the room.
+    fn floor_center( members: &Selection ) -> (i64, i64, i64) {
+        let floor = members.iter().map( |p| p.1 ).min().unwrap();
+        let cells = Vec::from_iter( members.iter().filter( |p| p.1 == floor ) );
+
+        let cx = cells.iter().map( |p| p.0 as f64 ).sum::<f64>() / cells.len() as f64;
+        let cz = cells.iter().map( |p| p.2 as f64 ).sum::<f64>() / cells.len() as f64;
+
+        let best = cells.iter().min_by( |a, b| {
+            let da = ( a.0 as f64 - cx ).powi(2) + ( a.2 as f64 - cz ).powi(2);
+            let db = ( b.0 as f64 - cx ).powi(2) + ( b.2 as f64 - cz ).powi(2);
+            da.total_cmp( &db ).then( b.0.cmp( &a.0 ) ).then( b.2.cmp( &a.2 ) )
+        } ).unwrap();
+
+        **best
+    }
+
+    // Random sequential acceptance over the shuffled candidates: a point is kept if no kept point is closer than the larger of their two radii.
+    pub fn poisson_sample( mut candidates: PosList, seed: i64, max_radius: f64, radius_of: impl Fn( &(i64, i64, i64) ) -> f64 ) -> PosList {
+        let mut random = ChaCha12Rng::seed_from_u64( seed as u64 );
+
+        candidates.sort();
+        candidates.shuffle( &mut random );
+
+        let bucket = max_radius.max( 1.0 );
+        let bucket_of = | p: &(i64, i64, i64) | -> (i64, i64, i64) {
+            ( ( p.0 as f64 / bucket ).floor() as i64, ( p.1 as f64 / bucket ).floor() as i64, ( p.2 as f64 / bucket ).floor() as i64 )
+        };
+
+        let mut accepted = Vec::<((i64, i64, i64), f64)>::new();
+        let mut buckets = HashMap::<(i64, i64, i64), Vec<usize>>::new();
+
+        for cand in candidates {
+            let r = radius_of( &cand );
+            let b = bucket_of( &cand );
+
+            let mut free = true;
+            'search: for bx in (b.0 - 1)..=(b.0 + 1) {
+                for by in (b.1 - 1)..=(b.1 + 1) {
+                    for bz in (b.2 - 1)..=(b.2 + 1) {
+                        let Some(idxs) = buckets.get( &(bx, by, bz) ) else { continue; };
+                        for idx in idxs {
+                            let (other, or) = accepted[*idx];
+                            let dist = ( ( cand.0 - other.0 ).pow(2) + ( cand.1 - other.1 ).pow(2) + ( cand.2 - other.2 ).pow(2) ) as f64;
+                            if dist < r.max(or).powi(2) {
+                                free = false;
+                                break 'search;
+                            }
+                        }
+                    }
+                }
+            }
+
+            if free {
+                buckets.entry( b ).or_default().push( accepted.len() );
+                accepted.push( ( cand, r ) );
+            }
+        }
+
+        Vec::from_iter( accepted.into_iter().map( |(p, _r)| p ) )
+    }
+
+    // Every cell gets the index of its nearest point (lowest index wins ties); borders are cells with a differently indexed face neighbor.
+    pub fn voronoi( points: &PosList, size: (usize, usize, usize), metric: DistanceMetric, y_weight: f64 ) -> (Array3<i64>, Selection) {
+        let regions = Array3::from_shape_fn( size, |(x, y, z)| {
+            let cell = ( x as i64, y as i64, z as i64 );
+            let mut best = -1;
+            let mut best_dist = f64::INFINITY;
+            for (idx, p) in points.iter().enumerate() {
+                let dist = Self::metric_distance( cell, *p, metric, y_weight );
+                if dist < best_dist {
+                    best_dist = dist;
+                    best = idx as i64;
+                }
+            }
+            best
+        } );
+
+        let mut borders = Box::new( HashSet::<(i64, i64, i64)>::new() );
+        for ((x, y, z), idx) in regions.indexed_iter() {
+            let neighbors = [ (1, 0, 0), (-1, 0, 0), (0, 1, 0), (0, -1, 0), (0, 0, 1), (0, 0, -1) ];
+            for (dx, dy, dz) in neighbors {
+                let Some(next) = in_grid( size, ( x as i64 + dx, y as i64 + dy, z as i64 + dz ) ) else {
+                    continue;
+                };
+                if regions[next] != *idx {
+                    borders.insert( ( x as i64, y as i64, z as i64 ) );
+                    break;
+                }
+            }
+        }
+
+        (regions, borders)
+    }
+
+    pub fn metric_distance( a: (i64, i64, i64), b: (i64, i64, i64), metric: DistanceMetric, y_weight: f64 ) -> f64 {
+        let dx = ( a.0 - b.0 ).abs() as f64;
+        let dy = ( a.1 - b.1 ).abs() as f64;
+        let dz = ( a.2 - b.2 ).abs() as f64;
+        match metric {
+            DistanceMetric::Euclidean => ( dx * dx + dy * dy + dz * dz ).sqrt(),
+            DistanceMetric::Manhattan => dx + dy + dz,
+            DistanceMetric::Chebyshev => dx.max( dy ).max( dz ),
+            DistanceMetric::AnisotropicY => ( dx * dx + ( dy * y_weight ).powi(2) + dz * dz ).sqrt(),
+        }
+    }
+
+    // Face-connected groups of selected cells, each sorted, ordered by their first cell.
+    pub fn label_components( sel: &Selection ) -> Vec<PosList> {
+        let mut cells = Vec::from_iter( sel.iter().cloned() );
+        cells.sort();
+
+        let mut seen = HashSet::<(i64, i64, i64)>::new();
+        let mut components = Vec::<PosList>::new();
+        let neighbors = [ (1, 0, 0), (-1, 0, 0), (0, 1, 0), (0, -1, 0), (0, 0, 1), (0, 0, -1) ];
+
+        for start in cells {
+            if !seen.insert( start ) {
+                continue;
+            }
+            let mut component = vec![ start ];
+            let mut queue = VecDeque::from( [ start ] );
+            while let Some(pos) = queue.pop_front() {
+                for (dx, dy, dz) in neighbors {
+                    let next = ( pos.0 + dx, pos.1 + dy, pos.2 + dz );
+                    if sel.contains( &next ) && seen.insert( next ) {
+                        component.push( next );
+                        queue.push_back( next );
+                    }
+                }
+            }
+            component.sort();
+            components.push( component );
+        }
+
+        components
+    }
+
+    // Corridor cells outside every room that sit level with and directly beside a room cell. Corridors are told apart by
+    // graph edge when edges are given, otherwise by connected pieces of corridor outside the rooms.
+    pub fn find_doors( corridor: &Selection, rooms: &[Room], edges: Option<&[RoomEdge]>, one_per_pair: bool ) -> Vec<Door> {
+        let in_room = | p: &(i64, i64, i64) | rooms.iter().any( |r| r.members.contains( p ) );
+
+        let outside = Box::new( HashSet::<(i64, i64, i64)>::from_iter( corridor.iter().filter( |p| !in_room( *p ) ).cloned() ) );
+
+        // corridors are told apart by graph edge where one covers the cell, otherwise by connected piece, numbered after the edges
+        let edge_of = | p: &(i64, i64, i64) | edges.and_then( |edges| edges.iter().position( |e| e.cells.contains( p ) ) );
+        let loose = Box::new( HashSet::<(i64, i64, i64)>::from_iter( outside.iter().filter( |p| edge_of( *p ).is_none() ).cloned() ) );
+        let first_piece = edges.map_or( 0, |edges| edges.len() );
+
+        let mut piece_of = HashMap::<(i64, i64, i64), usize>::new();
+        for (idx, piece) in Self::label_components( &loose ).into_iter().enumerate() {
+            for p in piece {
+                piece_of.insert( p, first_piece + idx );
+            }
+        }
+
+        let mut cells = Vec::from_iter( outside.iter().cloned() );
+        cells.sort();
+
+        let mut doors = Vec::<Door>::new();
+        let mut pairs = HashSet::<(usize, usize)>::new();
+
+        for pos in cells {
+            for (dx, dz) in [ (1, 0), (-1, 0), (0, 1), (0, -1) ] {
+                let next = ( pos.0 + dx, pos.1, pos.2 + dz );
+                let Some(room) = rooms.iter().position( |r| r.members.contains( &next ) ) else { continue; };
+
+                let corridor_id = edge_of( &pos ).unwrap_or_else( || piece_of[&pos] );
+                if one_per_pair && !pairs.insert( (room, corridor_id) ) {
+                    break;
+                }
+
+                doors.push( Door{ pos, orientation: orientation::facing( (dx, dz) ), room } );
+                break;
+            }
+        }
+
+        doors
+    }
+
+    // Stateless hash-based random value in [0, 1), so the result doesn't depend on the order cells are visited in.
+    pub fn cell_random( seed: i64, step: u64, pos: (i64, i64, i64) ) -> f64 {
+        let mut h = seed as u64;
+        for v in [ step, pos.0 as u64, pos.1 as u64, pos.2 as u64 ] {
+            h = splitmix64( h ^ v );
+        }
+        ( h >> 11 ) as f64 / ( 1u64 << 53 ) as f64
+    }
+
+    // Cells whose centers lie within the radius of a polyline given in fractional cell coordinates, clipped to the grid.
+    // Cells containing the line are always included, so a zero radius gives a connected thin line.
+    pub fn rasterize_polyline( points: &[(f64, f64, f64)], radius: f64, size: (usize, usize, usize) ) -> Selection {
+        let mut out = Box::new( HashSet::<(i64, i64, i64)>::new() );
+        let reach = radius.max(0.0).ceil() as i64;
+
+        let mut stamp = | c: (f64, f64, f64) | {
+            let base = ( c.0.floor() as i64, c.1.floor() as i64, c.2.floor() as i64 );
+            for dx in -reach..=reach {
+                for dy in -reach..=reach {
+                    for dz in -reach..=reach {
+                        let p = ( base.0 + dx, base.1 + dy, base.2 + dz );
+                        let d = ( ( p.0 as f64 + 0.5 - c.0 ).powi(2) + ( p.1 as f64 + 0.5 - c.1 ).powi(2) + ( p.2 as f64 + 0.5 - c.2 ).powi(2) ).sqrt();
+                        if in_grid( size, p ).is_some() && ( p == base || d <= radius ) {
+                            out.insert( p );
+                        }
+                    }
+                }
+            }
+        };
+
+        for (i, a) in points.iter().enumerate() {
+            let Some(b) = points.get( i + 1 ) else {
+                stamp( *a );
+                break;
+            };
+            // quarter-cell steps so no cell along the segment is skipped
+            let len = ( ( b.0 - a.0 ).powi(2) + ( b.1 - a.1 ).powi(2) + ( b.2 - a.2 ).powi(2) ).sqrt();
+            let steps = ( len * 4.0 ).ceil().max(1.0) as usize;
+            for s in 0..steps {
+                let t = s as f64 / steps as f64;
+                stamp( ( a.0 + ( b.0 - a.0 ) * t, a.1 + ( b.1 - a.1 ) * t, a.2 + ( b.2 - a.2 ) * t ) );
+            }
+        }
+
+        out
+    }
 }
 
+fn splitmix64( mut x: u64 ) -> u64 {
+    x = x.wrapping_add( 0x9E3779B97F4A7C15 );
+    x = ( x ^ ( x >> 30 ) ).wrapping_mul( 0xBF58476D1CE4E5B9 );
+    x = ( x ^ ( x >> 27 ) ).wrapping_mul( 0x94D049BB133111EB );
+    x ^ ( x >> 31 )
+}
@@ -1,51 +1,147 @@
 
 
+use std::collections::HashSet;
+
 use ndarray::Array3;
 
 use godot::prelude::*;
 use godot::classes::Expression;
 use godot::builtin::array;
 
-use crate::datagrid::{GridElement, Helper};
-use crate::resource::Neighborhood;
-use crate::algorithm::RectPrism;
+use crate::datagrid::{GridElement, Helper, Selection, wrap_pos};
+use crate::resource::{Neighborhood, EdgeMode};
+use crate::algorithm::{AlgorithmHelper, RectPrism};
+
 
 
+#[derive(GodotConvert, Var, Export, Default, PartialEq, Eq, Clone, Copy)]
+#[godot(via = i64)]
+pub enum CARuleMode {
+    #[default]
+    Expression,
+    Table,
+}
+
+// One row of a transition table: a cell in from_state whose neighbors include between min_count and max_count cells in count_state becomes to_state.
+// The first matching row wins. A row only counts one neighbor state, so rules conditioned on several states (e.g. at least 3 of A
+// and at most 1 of C) need the Expression rule mode instead.
+#[derive(GodotClass)]
+#[class(tool, init, base=Resource)]
+pub struct CATransition {
+    #[export]
+    pub from_state: i64,
+    #[export]
+    pub count_state: i64,
+    #[export]
+    pub min_count: i64,
+    // negative means no upper limit
+    #[export]
+    #[init(val = -1)]
+    pub max_count: i64,
+    #[export]
+    pub to_state: i64,
+}
 
 #[derive(GodotClass)]
 #[class(tool, init, base=Resource)]
 pub struct CellAutoRule {
     base: Base<Resource>,
     #[export]
+    rule_mode: CARuleMode,
+    #[export]
     neighborhood: Option<Gd<Neighborhood>>,
     #[export]
     result_expr: GString,
     #[export]
     provide_randomness: bool,
+    #[export]
+    edge_mode: EdgeMode,
+
+    #[export_group(name = "Selection fields")]
+    #[export]
+    initial_fill: f64,
+    #[export]
+    #[init(val = 5)]
+    birth_min: i64,
+    #[export]
+    #[init(val = 26)]
+    birth_max: i64,
+    #[export]
+    #[init(val = 4)]
+    survive_min: i64,
+    #[export]
+    #[init(val = 26)]
+    survive_max: i64,
+
+    #[export_group(name = "Table mode")]
+    #[export]
+    transitions: Array<Gd<CATransition>>,
 }
 
 impl CellAutoRule {
-    pub fn run(&self, data: GridElement, area: RectPrism, steps: usize) -> Result<GridElement, String> {
+    // Each step reads neighbors from the previous step's array and writes into a copy, so all cells update synchronously.
+    // With provide_randomness the result expression also receives 'rand', a value in [0, 1) derived from the seed, step and cell.
+    // Cells outside the mask (when one is given) keep their state.
+    pub fn run(&self, data: GridElement, area: RectPrism, size: (usize, usize, usize), steps: usize, seed: i64, mask: Option<&Selection>) -> Result<GridElement, String> {
+        if self.neighborhood.is_none() {
+            return Err( "CellAutoRule has no neighborhood supplied!".to_string() );
+        }
+
+        if self.rule_mode == CARuleMode::Table {
+            if let GridElement::Int( mut array ) = data {
+                for _i in 0..steps {
+                    array = self.run_tstep( &array, area.clone(), mask );
+                }
+                return Ok( GridElement::Int(array) );
+            } else {
+                return Err( "Invalid format for running a table CA on, must be Int".to_string() );
+            }
+        }
+
         if let GridElement::Int( mut array ) = data {
-            for _i in 0..steps {
-                let res = self.run_istep( array, area.clone() );
+            for i in 0..steps {
+                let res = self.run_istep( &array, area.clone(), seed, i as u64 + 1, mask );
                 if res.is_err() { return Err(res.err().unwrap()); }
                 array = res.unwrap();
             }
             return Ok( GridElement::Int(array) );
         } else if let GridElement::Float( mut array ) = data {
-            for _i in 0..steps {
-                let res = self.run_fstep( array, area.clone() );
+            for i in 0..steps {
+                let res = self.run_fstep( &array, area.clone(), seed, i as u64 + 1, mask );
                 if res.is_err() { return Err(res.err().unwrap()); }
                 array = res.unwrap();
             }
             return Ok( GridElement::Float(array) );
+        } else if let GridElement::Sel( mut select ) = data {
+            if self.initial_fill > 0.0 {
+                for x in area.min.0..area.max.0 {
+                    for y in area.min.1..area.max.1 {
+                        for z in area.min.2..area.max.2 {
+                            let pos = ( x as i64, y as i64, z as i64 );
+                            if !in_mask( mask, pos ) {
+                                continue;
+                            }
+                            if AlgorithmHelper::cell_random( seed, 0, pos ) < self.initial_fill {
+                                select.insert( pos );
+                            } else {
+                                select.remove( &pos );
+                            }
+                        }
+                    }
+                }
+            }
+            for i in 0..steps {
+                let res = self.run_sstep( &select, area.clone(), size, seed, i as u64 + 1, mask );
+                if res.is_err() { return Err(res.err().unwrap()); }
+                select = res.unwrap();
+            }
+            return Ok( GridElement::Sel(select) );
         } else {
-            return Err( "Invalid format for running CA on, must be Int or Float".to_string() );
+            return Err( "Invalid format for running CA on, must be Int, Float or Sel".to_string() );
         }
     }
 
-    fn run_istep(&self, mut array: Array3<i64>, area: RectPrism) -> Result<Array3<i64>, String> {
+    fn run_istep(&self, array: &Array3<i64>, area: RectPrism, seed: i64, step: u64, mask: Option<&Selection>) -> Result<Array3<i64>, String> {
         let help = Helper::new_alloc();
 
         let nh = self.neighborhood.as_ref().unwrap().bind();
@@ -65,22 +161,28 @@ impl CellAutoRule {
         let mut res_expression = Expression::new_gd();
         let pres : godot::global::Error;
         if self.result_expr.is_empty() {
-            pres = res_expression.parse_ex( &GString::from( "sum" ) ).input_names( &PackedStringArray::from( [GString::from("state"), GString::from("sum") ] ) ).done();
+            pres = res_expression.parse_ex( &GString::from( "sum" ) ).input_names( &PackedStringArray::from( [GString::from("state"), GString::from("sum"), GString::from("rand") ] ) ).done();
         } else {
-            pres = res_expression.parse_ex( &self.result_expr ).input_names( &PackedStringArray::from( [GString::from("state"), GString::from("sum") ] ) ).done();
+            pres = res_expression.parse_ex( &self.result_expr ).input_names( &PackedStringArray::from( [GString::from("state"), GString::from("sum"), GString::from("rand") ] ) ).done();
         }
 
         if !( pres == godot::global::Error::OK ) {
             return Err( format!( "Result expression parse error: {:?}", pres ) );
         }
 
+        let mut next = array.clone();
+
         for x in area.min.0..area.max.0 {
             for y in area.min.1..area.max.1 {
                 for z in area.min.2..area.max.2 {
+                    if !in_mask( mask, ( x as i64, y as i64, z as i64 ) ) {
+                        continue;
+                    }
+
                     let mut accumulator = nh.accumulator_base as i64;
 
                     for os in nh.offsets.iter_shared() {
-                        let checkpos = self.check_pos( ( x as i32 + os.x, y as i32 + os.y, z as i32 + os.z ) );
+                        let checkpos = wrap_pos( array.dim(), ( x as i32 + os.x, y as i32 + os.y, z as i32 + os.z ), self.edge_mode );
                         if checkpos.is_none() {
                             continue;
                         }
@@ -90,18 +192,21 @@ impl CellAutoRule {
                         accumulator = vari.to::<i64>();
                     }
 
-                    let svari = res_expression.execute_ex().inputs( &array![ &array[[ x as usize, y as usize, z as usize ]].to_variant(), &accumulator.to_variant() ] ).base_instance( &help.clone().upcast::<Object>() ).done();
-                    array[[ x as usize, y as usize, z as usize ]] = svari.to::<i64>();
+                    let rand = self.random_for( seed, step, ( x as i64, y as i64, z as i64 ) );
+                    let svari = res_expression.execute_ex().inputs( &array![ &array[[ x as usize, y as usize, z as usize ]].to_variant(), &accumulator.to_variant(), &rand.to_variant() ] ).base_instance( &help.clone().upcast::<Object>() ).done();
+                    next[[ x as usize, y as usize, z as usize ]] = svari.to::<i64>();
 
                 }
             }
         }
 
-        Ok(array)
+        help.free();
+
+        Ok(next)
     }
 
 
-    fn run_fstep(&self, mut array: Array3<f64>, area: RectPrism) -> Result<Array3<f64>, String> {
+    fn run_fstep(&self, array: &Array3<f64>, area: RectPrism, seed: i64, step: u64, mask: Option<&Selection>) -> Result<Array3<f64>, String> {
         let help = Helper::new_alloc();
 
         let nh = self.neighborhood.as_ref().unwrap().bind();
@@ -121,22 +226,28 @@ impl CellAutoRule {
         let mut res_expression = Expression::new_gd();
         let pres : godot::global::Error;
         if self.result_expr.is_empty() {
-            pres = res_expression.parse_ex( &GString::from( "sum" ) ).input_names( &PackedStringArray::from( [GString::from("state"), GString::from("sum") ] ) ).done();
+            pres = res_expression.parse_ex( &GString::from( "sum" ) ).input_names( &PackedStringArray::from( [GString::from("state"), GString::from("sum"), GString::from("rand") ] ) ).done();
         } else {
-            pres = res_expression.parse_ex( &self.result_expr ).input_names( &PackedStringArray::from( [GString::from("state"), GString::from("sum") ] ) ).done();
+            pres = res_expression.parse_ex( &self.result_expr ).input_names( &PackedStringArray::from( [GString::from("state"), GString::from("sum"), GString::from("rand") ] ) ).done();
         }
 
         if !( pres == godot::global::Error::OK ) {
             return Err( format!( "Result expression parse error: {:?}", pres ) );
         }
 
+        let mut next = array.clone();
+
         for x in area.min.0..area.max.0 {
             for y in area.min.1..area.max.1 {
                 for z in area.min.2..area.max.2 {
+                    if !in_mask( mask, ( x as i64, y as i64, z as i64 ) ) {
+                        continue;
+                    }
+
                     let mut accumulator = nh.accumulator_base;
 
                     for os in nh.offsets.iter_shared() {
-                        let checkpos = self.check_pos( ( x as i32 + os.x, y as i32 + os.y, z as i32 + os.z ) );
+                        let checkpos = wrap_pos( array.dim(), ( x as i32 + os.x, y as i32 + os.y, z as i32 + os.z ), self.edge_mode );
                         if checkpos.is_none() {
                             continue;
                         }
@@ -146,19 +257,157 @@ impl CellAutoRule {
                         accumulator = vari.to::<f64>();
                     }
 
-                    let svari = res_expression.execute_ex().inputs( &array![ &array[[ x as usize, y as usize, z as usize ]].to_variant(), &accumulator.to_variant() ] ).base_instance( &help.clone().upcast::<Object>() ).done();
-                    array[[ x as usize, y as usize, z as usize ]] = svari.to::<f64>();
+                    let rand = self.random_for( seed, step, ( x as i64, y as i64, z as i64 ) );
+                    let svari = res_expression.execute_ex().inputs( &array![ &array[[ x as usize, y as usize, z as usize ]].to_variant(), &accumulator.to_variant(), &rand.to_variant() ] ).base_instance( &help.clone().upcast::<Object>() ).done();
+                    next[[ x as usize, y as usize, z as usize ]] = svari.to::<f64>();
+
+                }
+            }
+        }
+
+        help.free();
+
+        Ok(next)
+    }
+
+
+    // Counts live neighbors natively; the result expression is only used when set, receiving 'state' as a bool and 'sum' as the count.
+    fn run_sstep(&self, select: &Selection, area: RectPrism, size: (usize, usize, usize), seed: i64, step: u64, mask: Option<&Selection>) -> Result<Selection, String> {
+        let help = Helper::new_alloc();
+
+        let nh = self.neighborhood.as_ref().unwrap().bind();
+
+        let mut res_expression = Expression::new_gd();
+        if !self.result_expr.is_empty() {
+            let pres = res_expression.parse_ex( &self.result_expr ).input_names( &PackedStringArray::from( [GString::from("state"), GString::from("sum"), GString::from("rand") ] ) ).done();
+
+            if !( pres == godot::global::Error::OK ) {
+                return Err( format!( "Result expression parse error: {:?}", pres ) );
+            }
+        }
+
+        let mut next = Box::new( HashSet::<(i64, i64, i64)>::new() );
+
+        // cells outside the apply area are carried over untouched
+        for pos in select.iter() {
+            let inside = pos.0 >= area.min.0 as i64 && pos.0 < area.max.0 as i64 &&
+                pos.1 >= area.min.1 as i64 && pos.1 < area.max.1 as i64 &&
+                pos.2 >= area.min.2 as i64 && pos.2 < area.max.2 as i64;
+            if !inside {
+                next.insert( *pos );
+            }
+        }
+
+        for x in area.min.0..area.max.0 {
+            for y in area.min.1..area.max.1 {
+                for z in area.min.2..area.max.2 {
+                    let pos = ( x as i64, y as i64, z as i64 );
+                    let state = select.contains( &pos );
+
+                    if !in_mask( mask, pos ) {
+                        if state {
+                            next.insert( pos );
+                        }
+                        continue;
+                    }
+
+                    let mut count: i64 = 0;
+
+                    for os in nh.offsets.iter_shared() {
+                        let checkpos = wrap_pos( size, ( x as i32 + os.x, y as i32 + os.y, z as i32 + os.z ), self.edge_mode );
+                        if checkpos.is_none() {
+                            continue;
+                        }
+                        let checkpos = checkpos.unwrap();
+
+                        if select.contains( &( checkpos[0] as i64, checkpos[1] as i64, checkpos[2] as i64 ) ) {
+                            count += 1;
+                        }
+                    }
+
+                    let alive : bool;
+                    if self.result_expr.is_empty() {
+                        if state {
+                            alive = count >= self.survive_min && count <= self.survive_max;
+                        } else {
+                            alive = count >= self.birth_min && count <= self.birth_max;
+                        }
+                    } else {
+                        let rand = self.random_for( seed, step, pos );
+                        let svari = res_expression.execute_ex().inputs( &array![ &state.to_variant(), &count.to_variant(), &rand.to_variant() ] ).base_instance( &help.clone().upcast::<Object>() ).done();
+                        alive = svari.to::<bool>();
+                    }
+
+                    if alive {
+                        next.insert( pos );
+                    }
+                }
+            }
+        }
+
+        help.free();
+
+        Ok(next)
+    }
+
+    fn run_tstep(&self, array: &Array3<i64>, area: RectPrism, mask: Option<&Selection>) -> Array3<i64> {
+        let nh = self.neighborhood.as_ref().unwrap().bind();
+
+        let table = Vec::from_iter( self.transitions.iter_shared().map( |t| {
+            let t = t.bind();
+            ( t.from_state, t.count_state, t.min_count, t.max_count, t.to_state )
+        } ) );
+        let offsets = Vec::from_iter( nh.offsets.iter_shared() );
+
+        let mut next = array.clone();
+        let mut neighbors = Vec::<i64>::with_capacity( offsets.len() );
+
+        for x in area.min.0..area.max.0 {
+            for y in area.min.1..area.max.1 {
+                for z in area.min.2..area.max.2 {
+                    if !in_mask( mask, ( x as i64, y as i64, z as i64 ) ) {
+                        continue;
+                    }
+
+                    let state = array[[ x, y, z ]];
 
+                    neighbors.clear();
+                    for os in offsets.iter() {
+                        if let Some(checkpos) = wrap_pos( array.dim(), ( x as i32 + os.x, y as i32 + os.y, z as i32 + os.z ), self.edge_mode ) {
+                            neighbors.push( array[checkpos] );
+                        }
+                    }
+
+                    for (from, counted, min, max, to) in table.iter() {
+                        if *from != state {
+                            continue;
+                        }
+                        let count = neighbors.iter().filter( |n| **n == *counted ).count() as i64;
+                        if count >= *min && ( *max < 0 || count <= *max ) {
+                            next[[ x, y, z ]] = *to;
+                            break;
+                        }
+                    }
                 }
             }
         }
 
-        Ok(array)
+        next
     }
 
-    // should probably have safety checking eventually but it'll do for now.'
-    fn check_pos( &self, pos: (i32, i32, i32) ) -> Option<[usize; 3]> {
-        Some([pos.0 as usize, pos.1 as usize, pos.2 as usize])
+    fn random_for(&self, seed: i64, step: u64, pos: (i64, i64, i64)) -> f64 {
+        if self.provide_randomness {
+            AlgorithmHelper::cell_random( seed, step, pos )
+        } else {
+            0.0
+        }
     }
 
 }
+
+fn in_mask( mask: Option<&Selection>, pos: (i64, i64, i64) ) -> bool {
+    match mask {
+        Some(m) => m.contains( &pos ),
+        None => true,
+    }
+}
@@ -0,0 +1,120 @@
+use ndarray::Array3;
+
+use godot::prelude::*;
+use godot::classes::FastNoiseLite;
+
+use crate::datagrid::{DataGrid, GridElement};
+
+
+
+#[derive(GodotConvert, Var, Export, Default, PartialEq, Eq, Clone, Copy)]
+#[godot(via = i64)]
+pub enum ClassifyMode {
+    #[default]
+    Rectangular,
+    NearestCentroid,
+}
+
+// A region of the classifier's field space. Rectangular mode uses min/max per field (missing entries are unbounded),
+// nearest-centroid mode uses the centroid, which needs one entry per field.
+#[derive(GodotClass)]
+#[class(tool, init, base=Resource)]
+pub struct ClassRegion {
+    #[export]
+    pub id: i64,
+    #[export]
+    pub min: PackedFloat64Array,
+    #[export]
+    pub max: PackedFloat64Array,
+    #[export]
+    pub centroid: PackedFloat64Array,
+}
+
+#[derive(GodotClass)]
+#[class(tool, init, base=Resource)]
+pub struct Classifier {
+    base: Base<Resource>,
+    #[export]
+    fields: Array<GString>,
+    #[export]
+    mode: ClassifyMode,
+    #[export]
+    regions: Array<Gd<ClassRegion>>,
+    #[export]
+    #[init(val = -1)]
+    default_id: i64,
+
+    #[export_group(name = "Blending")]
+    #[export]
+    blend_noise: Option<Gd<FastNoiseLite>>,
+    #[export]
+    blend_amount: f64,
+}
+
+impl Classifier {
+    // Every cell's values in the listed Int/Float fields form a point in field space, which is mapped to the id of the first
+    // region containing it (or the nearest centroid). Blending noise jitters each coordinate to roughen region boundaries.
+    pub fn classify( &self, grid: &DataGrid, seed: i64 ) -> Result<Array3<i64>, String> {
+        let mut sources = Vec::<&GridElement>::new();
+        for f in self.fields.iter_shared() {
+            let ge = grid.elements.get( &f.to_string() );
+            match ge {
+                Some( GridElement::Int(_) ) | Some( GridElement::Float(_) ) => { sources.push( ge.unwrap() ); },
+                _ => { return Err( format!( "Classifier field '{}' is missing or not an Int/Float field", f ) ); },
+            }
+        }
+        if sources.is_empty() {
+            return Err( "Classifier has no fields to classify by".to_string() );
+        }
+
+        let regions = Vec::from_iter( self.regions.iter_shared().map( |r| {
+            let r = r.bind();
+            ( r.id, r.min.to_vec(), r.max.to_vec(), r.centroid.to_vec() )
+        } ) );
+
+        if self.mode == ClassifyMode::NearestCentroid {
+            if let Some(bad) = regions.iter().find( |r| r.3.len() != sources.len() ) {
+                return Err( format!( "Classifier region {} needs a centroid with {} entries", bad.0, sources.len() ) );
+            }
+        }
+
+        let mut noise = self.blend_noise.clone();
+        if let Some(ref mut n) = noise {
+            n.set_seed( seed as i32 );
+        }
+
+        let mut point = vec![ 0.0; sources.len() ];
+
+        let result = Array3::from_shape_fn( grid.size, |(x, y, z)| {
+            for (i, ge) in sources.iter().enumerate() {
+                point[i] = match ge {
+                    GridElement::Int(arr) => arr[[ x, y, z ]] as f64,
+                    GridElement::Float(arr) => arr[[ x, y, z ]],
+                    _ => 0.0,
+                };
+                if let Some(ref n) = noise {
+                    // each dimension samples a far-apart part of the noise so the jitters are independent
+                    point[i] += n.get_noise_3d( x as f32 + 1000.0 * i as f32, y as f32, z as f32 ) as f64 * self.blend_amount;
+                }
+            }
+
+            match self.mode {
+                ClassifyMode::Rectangular => {
+                    regions.iter().find( |(_id, min, max, _c)| {
+                        point.iter().enumerate().all( |(i, v)| {
+                            min.get(i).is_none_or( |m| v >= m ) && max.get(i).is_none_or( |m| v <= m )
+                        } )
+                    } ).map_or( self.default_id, |r| r.0 )
+                },
+                ClassifyMode::NearestCentroid => {
+                    regions.iter().map( |(id, _min, _max, c)| {
+                        let dist : f64 = point.iter().zip( c.iter() ).map( |(v, cv)| ( v - cv ).powi(2) ).sum();
+                        ( *id, dist )
+                    } ).min_by( |a, b| a.1.total_cmp( &b.1 ) ).map_or( self.default_id, |r| r.0 )
+                },
+            }
+        } );
+
+        Ok( result )
+    }
+}
@@ -0,0 +1,60 @@
+use std::collections::BinaryHeap;
+
+use ndarray::Array3;
+
+use crate::datagrid::{Selection, PosList, in_grid};
+use crate::algorithm::HeapEntry;
+
+
+
+pub struct DistanceMap<'a> {
+    pub walkable: &'a Selection,
+    pub max_step: i64,
+    pub max_drop: i64,
+}
+
+impl DistanceMap<'_> {
+    // Travel distance from the nearest source over walkable cells, moving to any of the 8 horizontal neighbors while
+    // climbing at most max_step or dropping at most max_drop cells. Cells never reached are left at infinity.
+    pub fn compute( &self, sources: &PosList, size: (usize, usize, usize) ) -> Array3<f64> {
+        let mut dist = Array3::<f64>::from_elem( size, f64::INFINITY );
+        let mut open = BinaryHeap::<HeapEntry<(i64, i64, i64)>>::new();
+
+        for src in sources {
+            if let Some(idx) = in_grid( size, *src ) {
+                dist[idx] = 0.0;
+                open.push( HeapEntry{ cost: 0.0, item: *src } );
+            }
+        }
+
+        while let Some(entry) = open.pop() {
+            let pos = entry.item;
+            if entry.cost > dist[ in_grid( size, pos ).unwrap() ] {
+                continue;
+            }
+
+            for dx in -1..=1_i64 {
+                for dz in -1..=1_i64 {
+                    if dx == 0 && dz == 0 {
+                        continue;
+                    }
+                    for dy in -self.max_drop..=self.max_step {
+                        let next = ( pos.0 + dx, pos.1 + dy, pos.2 + dz );
+                        if !self.walkable.contains( &next ) {
+                            continue;
+                        }
+                        let Some(idx) = in_grid( size, next ) else { continue; };
+
+                        let cost = entry.cost + ( ( dx * dx + dy * dy + dz * dz ) as f64 ).sqrt();
+                        if cost < dist[idx] {
+                            dist[idx] = cost;
+                            open.push( HeapEntry{ cost, item: next } );
+                        }
+                    }
+                }
+            }
+        }
+
+        dist
+    }
+}
@@ -0,0 +1,157 @@
+use ndarray::Array2;
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+
+
+
+pub struct Erosion {
+    pub iterations: usize,
+    pub thermal_passes: usize,
+    pub lifetime: usize,
+    pub erosion_rate: f64,
+    pub deposition_rate: f64,
+    pub evaporation_rate: f64,
+    pub inertia: f64,
+    pub capacity: f64,
+    pub talus: f64,
+}
+
+impl Erosion {
+    // Droplet-based hydraulic erosion: each iteration spawns one droplet that runs downhill, picking up sediment while it
+    // has spare capacity and dropping it when slowing down or climbing. Returns how much water passed over each cell.
+    pub fn hydraulic( &self, heights: &mut Array2<f64>, seed: i64 ) -> Array2<f64> {
+        let mut random = ChaCha12Rng::seed_from_u64( seed as u64 );
+        let dim = heights.dim();
+        let mut flow = Array2::<f64>::zeros( dim );
+
+        if dim.0 < 2 || dim.1 < 2 {
+            return flow;
+        }
+
+        for _i in 0..self.iterations {
+            let mut pos = ( random.random_range( 0.0..( dim.0 - 1 ) as f64 ), random.random_range( 0.0..( dim.1 - 1 ) as f64 ) );
+            let mut dir = ( 0.0, 0.0 );
+            let mut speed = 1.0;
+            let mut water = 1.0;
+            let mut sediment = 0.0;
+
+            for _step in 0..self.lifetime {
+                let cell = ( pos.0.floor() as usize, pos.1.floor() as usize );
+                let (height, grad) = Self::sample( heights, pos );
+
+                dir = ( dir.0 * self.inertia - grad.0 * ( 1.0 - self.inertia ), dir.1 * self.inertia - grad.1 * ( 1.0 - self.inertia ) );
+                let len : f64 = ( dir.0 * dir.0 + dir.1 * dir.1 ).sqrt();
+                if len < 1e-9 {
+                    break;
+                }
+                dir = ( dir.0 / len, dir.1 / len );
+
+                let next = ( pos.0 + dir.0, pos.1 + dir.1 );
+                if next.0 < 0.0 || next.1 < 0.0 || next.0 >= ( dim.0 - 1 ) as f64 || next.1 >= ( dim.1 - 1 ) as f64 {
+                    break;
+                }
+
+                flow[cell] += water;
+
+                let dh = Self::sample( heights, next ).0 - height;
+                let capacity = ( -dh * speed * water * self.capacity ).max( 0.01 );
+
+                if sediment > capacity || dh > 0.0 {
+                    // climbing fills the pit behind the droplet, otherwise only the excess settles
+                    let deposit = if dh > 0.0 { dh.min( sediment ) } else { ( sediment - capacity ) * self.deposition_rate };
+                    sediment -= deposit;
+                    Self::spread( heights, pos, deposit );
+                } else {
+                    let erode = ( ( capacity - sediment ) * self.erosion_rate ).min( -dh );
+                    sediment += erode;
+                    Self::spread( heights, pos, -erode );
+                }
+
+                speed = ( speed * speed + dh.abs() * 4.0 ).sqrt();
+                water *= 1.0 - self.evaporation_rate;
+                pos = next;
+            }
+        }
+
+        flow
+    }
+
+    // Thermal erosion: material slides from each cell to lower neighbors wherever the height difference exceeds the talus threshold.
+    // Returns how much material left each cell in total.
+    pub fn thermal( &self, heights: &mut Array2<f64> ) -> Array2<f64> {
+        let dim = heights.dim();
+        let mut moved = Array2::<f64>::zeros( dim );
+
+        let neighbors = [ (1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (1, -1), (-1, 1), (-1, -1) ];
+
+        for _i in 0..self.thermal_passes {
+            let mut delta = Array2::<f64>::zeros( dim );
+
+            for x in 0..dim.0 {
+                for z in 0..dim.1 {
+                    let h = heights[[ x, z ]];
+                    let mut lower = Vec::<((usize, usize), f64)>::new();
+                    let mut max_diff: f64 = 0.0;
+                    let mut total = 0.0;
+
+                    for (dx, dz) in neighbors {
+                        let (nx, nz) = ( x as i64 + dx, z as i64 + dz );
+                        if nx < 0 || nz < 0 || nx >= dim.0 as i64 || nz >= dim.1 as i64 {
+                            continue;
+                        }
+                        let diff = h - heights[[ nx as usize, nz as usize ]];
+                        if diff > self.talus {
+                            lower.push( ( ( nx as usize, nz as usize ), diff ) );
+                            max_diff = max_diff.max( diff );
+                            total += diff;
+                        }
+                    }
+
+                    if lower.is_empty() {
+                        continue;
+                    }
+
+                    let amount = 0.5 * ( max_diff - self.talus );
+                    delta[[ x, z ]] -= amount;
+                    moved[[ x, z ]] += amount;
+                    for (npos, diff) in lower {
+                        delta[npos] += amount * diff / total;
+                    }
+                }
+            }
+
+            *heights += &delta;
+        }
+
+        moved
+    }
+
+    // Bilinear height and gradient at a fractional position.
+    fn sample( heights: &Array2<f64>, pos: (f64, f64) ) -> (f64, (f64, f64)) {
+        let (cx, cz) = ( pos.0.floor() as usize, pos.1.floor() as usize );
+        let (u, v) = ( pos.0 - cx as f64, pos.1 - cz as f64 );
+
+        let h00 = heights[[ cx, cz ]];
+        let h10 = heights[[ cx + 1, cz ]];
+        let h01 = heights[[ cx, cz + 1 ]];
+        let h11 = heights[[ cx + 1, cz + 1 ]];
+
+        let gx = ( h10 - h00 ) * ( 1.0 - v ) + ( h11 - h01 ) * v;
+        let gz = ( h01 - h00 ) * ( 1.0 - u ) + ( h11 - h10 ) * u;
+        let height = h00 * ( 1.0 - u ) * ( 1.0 - v ) + h10 * u * ( 1.0 - v ) + h01 * ( 1.0 - u ) * v + h11 * u * v;
+
+        ( height, ( gx, gz ) )
+    }
+
+    // Adds an amount to the four cells around a fractional position, weighted by proximity.
+    fn spread( heights: &mut Array2<f64>, pos: (f64, f64), amount: f64 ) {
+        let (cx, cz) = ( pos.0.floor() as usize, pos.1.floor() as usize );
+        let (u, v) = ( pos.0 - cx as f64, pos.1 - cz as f64 );
+
+        heights[[ cx, cz ]] += amount * ( 1.0 - u ) * ( 1.0 - v );
+        heights[[ cx + 1, cz ]] += amount * u * ( 1.0 - v );
+        heights[[ cx, cz + 1 ]] += amount * ( 1.0 - u ) * v;
+        heights[[ cx + 1, cz + 1 ]] += amount * u * v;
+    }
+}
@@ -0,0 +1,132 @@
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use crate::datagrid::{Selection, PosList, in_grid};
+use crate::algorithm::HeapEntry;
+
+const NEIGHBORS: [(i64, i64, i64); 6] = [ (1, 0, 0), (-1, 0, 0), (0, 1, 0), (0, -1, 0), (0, 0, 1), (0, 0, -1) ];
+
+
+pub struct FluidFill<'a> {
+    pub solid: &'a Selection,
+    pub size: (usize, usize, usize),
+}
+
+impl FluidFill<'_> {
+    // Fills every open cell up to the level, or up to the height its basin spills over towards the grid's sides and bottom if that's lower.
+    pub fn fill_to_level( &self, level: i64 ) -> Selection {
+        let mut water = Box::new( HashSet::<(i64, i64, i64)>::new() );
+        let mut spill = HashMap::<(i64, i64, i64), i64>::new();
+        let mut open = BinaryHeap::<HeapEntry<(i64, i64, i64)>>::new();
+        let top = level.min( self.size.1 as i64 - 1 );
+
+        // water runs off wherever an open cell borders the sides or bottom
+        for x in 0..self.size.0 as i64 {
+            for y in 0..self.size.1 as i64 {
+                for z in 0..self.size.2 as i64 {
+                    let pos = (x, y, z);
+                    if self.solid.contains( &pos ) {
+                        continue;
+                    }
+                    let edge = NEIGHBORS.iter().any( |(dx, dy, dz)| {
+                        let next = ( x + dx, y + dy, z + dz );
+                        in_grid( self.size, next ).is_none() && next.1 < self.size.1 as i64
+                    } );
+                    if edge {
+                        open.push( HeapEntry{ cost: y as f64, item: pos } );
+                    }
+                }
+            }
+        }
+
+        // the lowest rim each cell has to climb over to reach a run off
+        while let Some(entry) = open.pop() {
+            let pos = entry.item;
+            if spill.contains_key( &pos ) {
+                continue;
+            }
+            let height = entry.cost as i64;
+            spill.insert( pos, height );
+            for (dx, dy, dz) in NEIGHBORS {
+                let next = ( pos.0 + dx, pos.1 + dy, pos.2 + dz );
+                if in_grid( self.size, next ).is_none() || self.solid.contains( &next ) || spill.contains_key( &next ) {
+                    continue;
+                }
+                open.push( HeapEntry{ cost: height.max( next.1 ) as f64, item: next } );
+            }
+        }
+
+        for x in 0..self.size.0 as i64 {
+            for y in 0..=top {
+                for z in 0..self.size.2 as i64 {
+                    let pos = (x, y, z);
+                    if !self.solid.contains( &pos ) && spill.get( &pos ).is_none_or( |h| y < *h ) {
+                        water.insert( pos );
+                    }
+                }
+            }
+        }
+
+        water
+    }
+
+    // Water from each source falls until it lands on something solid, then rises for as long as the basin it fills stays sealed.
+    // Sources that fall out of the grid, or land where water runs straight off, produce nothing.
+    pub fn fill_from_sources( &self, sources: &PosList, max_level: Option<i64> ) -> Selection {
+        let mut water = Box::new( HashSet::<(i64, i64, i64)>::new() );
+        let top = max_level.unwrap_or( self.size.1 as i64 - 1 ).min( self.size.1 as i64 - 1 );
+
+        for source in sources {
+            if in_grid( self.size, *source ).is_none() || self.solid.contains( source ) {
+                continue;
+            }
+
+            let mut landing = *source;
+            while landing.1 >= 0 && !self.solid.contains( &( landing.0, landing.1 - 1, landing.2 ) ) {
+                landing = ( landing.0, landing.1 - 1, landing.2 );
+            }
+            if landing.1 < 0 {
+                continue;
+            }
+
+            water.extend( self.rise( landing, top ) );
+        }
+
+        water
+    }
+
+    // Grows the basin lowest cell first, one level at a time, and returns it as of the last level that didn't leak.
+    fn rise( &self, landing: (i64, i64, i64), top: i64 ) -> Vec<(i64, i64, i64)> {
+        let mut filled = Vec::<(i64, i64, i64)>::new();
+        let mut seen = HashSet::<(i64, i64, i64)>::from( [ landing ] );
+        let mut open = BinaryHeap::<HeapEntry<(i64, i64, i64)>>::new();
+        let mut sealed = 0;
+
+        open.push( HeapEntry{ cost: landing.1 as f64, item: landing } );
+
+        for level in landing.1..=top {
+            while open.peek().is_some_and( |e| e.item.1 <= level ) {
+                let pos = open.pop().unwrap().item;
+                filled.push( pos );
+                for (dx, dy, dz) in NEIGHBORS {
+                    let next = ( pos.0 + dx, pos.1 + dy, pos.2 + dz );
+                    if self.solid.contains( &next ) || seen.contains( &next ) {
+                        continue;
+                    }
+                    // only the sides and bottom can leak, cells above the grid are never reached
+                    if in_grid( self.size, next ).is_none() {
+                        if next.1 < self.size.1 as i64 {
+                            filled.truncate( sealed );
+                            return filled;
+                        }
+                        continue;
+                    }
+                    seen.insert( next );
+                    open.push( HeapEntry{ cost: next.1 as f64, item: next } );
+                }
+            }
+            sealed = filled.len();
+        }
+
+        filled
+    }
+}
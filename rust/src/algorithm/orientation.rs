@@ -0,0 +1,148 @@
+// GridMap orientation indices for 0, 90, 180 and 270 degree turns around Y, each turn taking +X towards -Z.
+pub const Y_TURNS: [i64; 4] = [0, 16, 10, 22];
+
+// Orientation that points an item's local +Z axis along a horizontal unit direction given as (x, z).
+pub fn facing( dir: (i64, i64) ) -> i64 {
+    match dir {
+        (0, 1) => Y_TURNS[0],
+        (1, 0) => Y_TURNS[1],
+        (0, -1) => Y_TURNS[2],
+        _ => Y_TURNS[3],
+    }
+}
+
+// Basis rows for every GridMap orientation index, in the same order as Godot's table.
+const BASES: [[[i8; 3]; 3]; 24] = [
+    [ [1, 0, 0], [0, 1, 0], [0, 0, 1] ],
+    [ [0, -1, 0], [1, 0, 0], [0, 0, 1] ],
+    [ [-1, 0, 0], [0, -1, 0], [0, 0, 1] ],
+    [ [0, 1, 0], [-1, 0, 0], [0, 0, 1] ],
+    [ [1, 0, 0], [0, 0, -1], [0, 1, 0] ],
+    [ [0, 0, 1], [1, 0, 0], [0, 1, 0] ],
+    [ [-1, 0, 0], [0, 0, 1], [0, 1, 0] ],
+    [ [0, 0, -1], [-1, 0, 0], [0, 1, 0] ],
+    [ [1, 0, 0], [0, -1, 0], [0, 0, -1] ],
+    [ [0, 1, 0], [1, 0, 0], [0, 0, -1] ],
+    [ [-1, 0, 0], [0, 1, 0], [0, 0, -1] ],
+    [ [0, -1, 0], [-1, 0, 0], [0, 0, -1] ],
+    [ [1, 0, 0], [0, 0, 1], [0, -1, 0] ],
+    [ [0, 0, -1], [1, 0, 0], [0, -1, 0] ],
+    [ [-1, 0, 0], [0, 0, -1], [0, -1, 0] ],
+    [ [0, 0, 1], [-1, 0, 0], [0, -1, 0] ],
+    [ [0, 0, 1], [0, 1, 0], [-1, 0, 0] ],
+    [ [0, -1, 0], [0, 0, 1], [-1, 0, 0] ],
+    [ [0, 0, -1], [0, -1, 0], [-1, 0, 0] ],
+    [ [0, 1, 0], [0, 0, -1], [-1, 0, 0] ],
+    [ [0, 0, 1], [0, -1, 0], [1, 0, 0] ],
+    [ [0, 1, 0], [0, 0, 1], [1, 0, 0] ],
+    [ [0, 0, -1], [0, 1, 0], [1, 0, 0] ],
+    [ [0, -1, 0], [0, 0, -1], [1, 0, 0] ],
+];
+
+fn multiply( a: &[[i8; 3]; 3], b: &[[i8; 3]; 3] ) -> [[i8; 3]; 3] {
+    let mut out = [ [0; 3]; 3 ];
+    for i in 0..3 {
+        for j in 0..3 {
+            out[i][j] = ( 0..3 ).map( |k| a[i][k] * b[k][j] ).sum();
+        }
+    }
+    out
+}
+
+fn index_of( basis: &[[i8; 3]; 3] ) -> Option<i64> {
+    BASES.iter().position( |b| b == basis ).map( |i| i as i64 )
+}
+
+// Orientation after turning the item around Y by the given number of quarter turns. Invalid indices are returned unchanged.
+pub fn rotate_y( orientation: i64, turns: i64 ) -> i64 {
+    if !( 0..24 ).contains( &orientation ) {
+        return orientation;
+    }
+    let turn = BASES[ Y_TURNS[ turns.rem_euclid(4) as usize ] as usize ];
+    index_of( &multiply( &turn, &BASES[ orientation as usize ] ) ).unwrap_or( orientation )
+}
+
+// Orientation of the item after the grid is mirrored along an axis (0 = X, 1 = Y, 2 = Z). A true reflection can't be
+// stored as an orientation, so the item is mirrored in its own frame as well, which keeps symmetric pieces looking right.
+pub fn mirror( orientation: i64, axis: usize ) -> i64 {
+    if !( 0..24 ).contains( &orientation ) {
+        return orientation;
+    }
+    let mut flip = BASES[0];
+    flip[axis][axis] = -1;
+    index_of( &multiply( &multiply( &flip, &BASES[ orientation as usize ] ), &flip ) ).unwrap_or( orientation )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a quarter turn around Y taking +X to -Z, written out rather than read from the table
+    const QUARTER_TURN: [[i8; 3]; 3] = [ [0, 0, 1], [0, 1, 0], [-1, 0, 0] ];
+
+    #[test]
+    fn bases_are_distinct_rotations() {
+        for (i, b) in BASES.iter().enumerate() {
+            let det = b[0][0] as i32 * ( b[1][1] as i32 * b[2][2] as i32 - b[1][2] as i32 * b[2][1] as i32 )
+                - b[0][1] as i32 * ( b[1][0] as i32 * b[2][2] as i32 - b[1][2] as i32 * b[2][0] as i32 )
+                + b[0][2] as i32 * ( b[1][0] as i32 * b[2][1] as i32 - b[1][1] as i32 * b[2][0] as i32 );
+            assert_eq!( det, 1, "basis {} isn't a rotation", i );
+            assert_eq!( index_of( b ), Some( i as i64 ), "basis {} appears twice", i );
+        }
+    }
+
+    #[test]
+    fn y_turns_match_table() {
+        let mut turned = BASES[0];
+        for turns in 0..4 {
+            assert_eq!( BASES[ Y_TURNS[turns] as usize ], turned );
+            turned = multiply( &QUARTER_TURN, &turned );
+        }
+    }
+
+    #[test]
+    fn facing_points_local_z() {
+        for (dx, dz) in [ (0, 1), (1, 0), (0, -1), (-1, 0) ] {
+            let b = BASES[ facing( (dx, dz) ) as usize ];
+            assert_eq!( ( b[0][2] as i64, b[1][2] as i64, b[2][2] as i64 ), ( dx, 0, dz ) );
+        }
+    }
+
+    #[test]
+    fn rotate_y_matches_basis_product() {
+        for i in 0..24 {
+            assert_eq!( BASES[ rotate_y( i, 1 ) as usize ], multiply( &QUARTER_TURN, &BASES[ i as usize ] ) );
+        }
+    }
+
+    #[test]
+    fn four_turns_are_identity() {
+        for i in 0..24 {
+            let mut o = i;
+            for _ in 0..4 {
+                o = rotate_y( o, 1 );
+            }
+            assert_eq!( o, i );
+            assert_eq!( rotate_y( i, 4 ), i );
+            assert_eq!( rotate_y( rotate_y( i, 3 ), 1 ), i );
+        }
+    }
+
+    #[test]
+    fn mirror_matches_basis_product() {
+        for axis in 0..3 {
+            let mut flip = [ [1, 0, 0], [0, 1, 0], [0, 0, 1] ];
+            flip[axis][axis] = -1;
+            for i in 0..24 {
+                assert_eq!( BASES[ mirror( i, axis ) as usize ], multiply( &multiply( &flip, &BASES[ i as usize ] ), &flip ) );
+                assert_eq!( mirror( mirror( i, axis ), axis ), i );
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_indices_pass_through() {
+        assert_eq!( rotate_y( -1, 1 ), -1 );
+        assert_eq!( mirror( 24, 0 ), 24 );
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::{HashSet, HashMap};
+use std::collections::{BinaryHeap, HashSet, HashMap};
 
 use ndarray::Array3;
 
@@ -8,7 +8,8 @@ use grid_ray::GridRayIter3;
 use grid_ray::ilattice::glam::Vec3A;
 
 
-use crate::datagrid::Selection;
+use crate::datagrid::{Selection, in_grid};
+use crate::algorithm::{AlgorithmHelper, HeapEntry};
 
 
 
@@ -177,3 +178,98 @@ impl SearchMap {
         ( mid.sqrt() + self.vertical_skew.powi(2) * ( a[1] as f32 - b[1] as f32 ).powi(2) ).sqrt()
     }
 }
+
+
+// Joins the pieces of a selection with the corridors that are cheapest to carve through a weights field. Each cell costs its
+// weight to enter, times vertical_cost for moves along Y; cells with infinite weight can't be carved.
+pub struct Connector {
+    pub weights: Array3<f64>,
+    pub vertical_cost: f64,
+}
+
+impl Connector {
+    // Drops pieces smaller than min_size, then repeatedly grows a Dijkstra search out of everything joined so far until it
+    // reaches another piece, carving the path it took. Returns the joined selection, the carved cells and how many pieces
+    // couldn't be reached at all; those are left out of the joined selection.
+    pub fn ensure_connected( &self, open: &Selection, min_size: usize ) -> (Selection, Selection, usize) {
+        let mut pieces = AlgorithmHelper::label_components( open );
+        pieces.retain( |p| p.len() >= min_size );
+
+        let mut joined = Box::new( HashSet::<(i64, i64, i64)>::new() );
+        let mut carved = Box::new( HashSet::<(i64, i64, i64)>::new() );
+
+        let Some(largest) = ( 0..pieces.len() ).max_by_key( |i| ( pieces[*i].len(), usize::MAX - *i ) ) else { return (joined, carved, 0); };
+        joined.extend( pieces.remove( largest ) );
+
+        let mut owner = HashMap::<(i64, i64, i64), usize>::new();
+        for (idx, piece) in pieces.iter().enumerate() {
+            for p in piece {
+                owner.insert( *p, idx );
+            }
+        }
+        let mut remaining = pieces.len();
+
+        while remaining > 0 {
+            let Some((reached, path)) = self.cheapest_path( &joined, &owner ) else { break; };
+
+            let idx = owner[&reached];
+            owner.retain( |_p, i| *i != idx );
+            remaining -= 1;
+
+            carved.extend( path.iter().filter( |c| !open.contains( *c ) ).cloned() );
+            joined.extend( path );
+            joined.extend( pieces[idx].iter().cloned() );
+        }
+
+        (joined, carved, remaining)
+    }
+
+    // The first cell of another piece the search reaches, and the cells on the way there from the joined selection.
+    fn cheapest_path( &self, joined: &Selection, owner: &HashMap<(i64, i64, i64), usize> ) -> Option<((i64, i64, i64), Vec<(i64, i64, i64)>)> {
+        let mut cost = HashMap::<(i64, i64, i64), f64>::new();
+        let mut parent = HashMap::<(i64, i64, i64), (i64, i64, i64)>::new();
+        let mut open = BinaryHeap::<HeapEntry<(i64, i64, i64)>>::new();
+
+        for p in joined.iter() {
+            cost.insert( *p, 0.0 );
+            open.push( HeapEntry{ cost: 0.0, item: *p } );
+        }
+
+        let neighbors = [ (1, 0, 0), (-1, 0, 0), (0, 1, 0), (0, -1, 0), (0, 0, 1), (0, 0, -1) ];
+
+        while let Some(entry) = open.pop() {
+            let pos = entry.item;
+            if entry.cost > cost[&pos] {
+                continue;
+            }
+            if owner.contains_key( &pos ) {
+                let mut path = vec![];
+                let mut current = pos;
+                while let Some(prev) = parent.get( &current ) {
+                    path.push( *prev );
+                    current = *prev;
+                }
+                path.retain( |p| !joined.contains( p ) );
+                return Some( (pos, path) );
+            }
+
+            for (dx, dy, dz) in neighbors {
+                let next = ( pos.0 + dx, pos.1 + dy, pos.2 + dz );
+                let Some(idx) = in_grid( self.weights.dim(), next ) else { continue; };
+                let weight = self.weights[idx];
+                if !weight.is_finite() {
+                    continue;
+                }
+                let step = weight.max(0.0) * if dy != 0 { self.vertical_cost } else { 1.0 };
+                let next_cost = entry.cost + step;
+                if cost.get( &next ).is_none_or( |c| next_cost < *c ) {
+                    cost.insert( next, next_cost );
+                    parent.insert( next, pos );
+                    open.push( HeapEntry{ cost: next_cost, item: next } );
+                }
+            }
+        }
+
+        None
+    }
+}
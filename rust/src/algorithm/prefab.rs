@@ -0,0 +1,128 @@
+use godot::prelude::*;
+use godot::classes::GridMap;
+
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+
+use crate::datagrid::{Selection, Room, in_grid};
+use crate::algorithm::orientation;
+
+
+
+// Cells of a hand-authored GridMap, relative to its own origin: position, mesh item and orientation.
+pub struct Prefab {
+    pub cells: Vec<((i64, i64, i64), i64, i64)>,
+}
+
+impl Prefab {
+    // The scene's root has to be a GridMap; it's only instanced long enough to read its cells.
+    pub fn from_scene( scene: &Gd<PackedScene> ) -> Result<Self, String> {
+        let Some(node) = scene.instantiate() else {
+            return Err( format!( "couldn't instance prefab scene '{}'", scene.get_path() ) );
+        };
+        let grid = match node.try_cast::<GridMap>() {
+            Ok(grid) => grid,
+            Err(node) => {
+                node.free();
+                return Err( format!( "prefab scene '{}' isn't a GridMap", scene.get_path() ) );
+            },
+        };
+
+        let cells = grid.get_used_cells().iter_shared()
+            .map( |c| ( ( c.x as i64, c.y as i64, c.z as i64 ), grid.get_cell_item( c ) as i64, grid.get_cell_item_orientation( c ) as i64 ) )
+            .collect();
+        grid.free();
+
+        Ok( Self{ cells } )
+    }
+
+    // Quarter turns around the prefab's origin, each taking +X towards -Z like GridMap orientations do.
+    pub fn rotated( &self, turns: i64 ) -> Vec<((i64, i64, i64), i64, i64)> {
+        self.cells.iter().map( |&(p, mesh, orient)| {
+            let mut q = p;
+            for _ in 0..turns.rem_euclid(4) {
+                q = ( q.2, q.1, -q.0 );
+            }
+            ( q, mesh, orientation::rotate_y( orient, turns ) )
+        } ).collect()
+    }
+}
+
+// Where a prefab ended up: which prefab, and the grid cells it covers with their mesh and orientation.
+pub struct Placement {
+    pub prefab: usize,
+    pub cells: Vec<((i64, i64, i64), i64, i64)>,
+}
+
+pub struct PrefabStamper<'a> {
+    pub prefabs: &'a [Prefab],
+    pub size: (usize, usize, usize),
+    pub turns: Option<i64>,
+}
+
+impl<'a> PrefabStamper<'a> {
+    // Each anchor tries the prefab and rotation combinations in random order and keeps the first that fits without
+    // leaving the grid, its room, or overlapping cells already occupied.
+    pub fn stamp( &self, anchors: &[Anchor], occupied: &mut Selection, seed: i64 ) -> Vec<Placement> {
+        let mut random = ChaCha12Rng::seed_from_u64( seed as u64 );
+        let mut placements = vec![];
+
+        for anchor in anchors.iter() {
+            let mut options : Vec<(usize, i64)> = ( 0..self.prefabs.len() )
+                .flat_map( |i| match self.turns {
+                    Some(t) => vec![ (i, t) ],
+                    None => ( 0..4 ).map( |t| (i, t) ).collect(),
+                } )
+                .collect();
+            options.shuffle( &mut random );
+
+            for (idx, turns) in options {
+                let cells = self.prefabs[idx].rotated( turns );
+                if cells.is_empty() {
+                    continue;
+                }
+                let offset = anchor.offset( &cells );
+                let placed : Vec<_> = cells.iter().map( |&(p, m, o)| ( ( p.0 + offset.0, p.1 + offset.1, p.2 + offset.2 ), m, o ) ).collect();
+                if placed.iter().all( |(p, _, _)| in_grid( self.size, *p ).is_some() && !occupied.contains( p ) && anchor.allows( *p ) ) {
+                    occupied.extend( placed.iter().map( |(p, _, _)| *p ) );
+                    placements.push( Placement{ prefab: idx, cells: placed } );
+                    break;
+                }
+            }
+        }
+
+        placements
+    }
+}
+
+// A point puts the prefab's origin on it; a room centers the prefab's footprint on the room and rests it on the room's floor.
+pub enum Anchor<'a> {
+    Point( (i64, i64, i64) ),
+    Room( &'a Room ),
+}
+
+impl<'a> Anchor<'a> {
+    fn offset( &self, cells: &[((i64, i64, i64), i64, i64)] ) -> (i64, i64, i64) {
+        match self {
+            Anchor::Point(p) => *p,
+            Anchor::Room(room) => {
+                let min = |f: fn( &(i64, i64, i64) ) -> i64| cells.iter().map( |(p, _, _)| f( p ) ).min().unwrap_or(0);
+                let max = |f: fn( &(i64, i64, i64) ) -> i64| cells.iter().map( |(p, _, _)| f( p ) ).max().unwrap_or(0);
+                let floor = room.members.iter().map( |p| p.1 ).min().unwrap_or( room.center.1 );
+                (
+                    room.center.0 - ( min( |p| p.0 ) + max( |p| p.0 ) ).div_euclid(2),
+                    floor - min( |p| p.1 ),
+                    room.center.2 - ( min( |p| p.2 ) + max( |p| p.2 ) ).div_euclid(2),
+                )
+            },
+        }
+    }
+
+    fn allows( &self, p: (i64, i64, i64) ) -> bool {
+        match self {
+            Anchor::Point(_) => true,
+            Anchor::Room(room) => room.members.contains( &p ),
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+
+use crate::datagrid::{RoomGraph, EdgeKind, PosList};
+
+
+
+pub struct Progression {
+    pub start: usize,
+    pub goal: usize,
+    pub keys: PosList,
+    pub locks: PosList,
+}
+
+// Picks the two rooms farthest apart in hops as start and goal, locks bridge edges spread along the path between them and
+// places key i in a room reachable from the start while only locks below i are open, so the keys can always be
+// collected in order. Lock and key ids are their indices in the returned lists.
+pub fn lock_and_key( graph: &mut RoomGraph, lock_count: usize, seed: i64 ) -> Result<Progression, String> {
+    let mut random = ChaCha12Rng::seed_from_u64( seed as u64 );
+
+    let mut ends : Option<(usize, usize, usize)> = None;
+    for a in 0..graph.rooms.len() {
+        let (hops, _parent) = graph.search( a, |_e| true );
+        for (b, h) in hops.into_iter().enumerate() {
+            if let Some(h) = h && ends.is_none_or( |e| h > e.2 ) {
+                ends = Some( (a, b, h) );
+            }
+        }
+    }
+    let Some((start, goal, length)) = ends else { return Err( "Room graph has no rooms".to_string() ) };
+    if length == 0 {
+        return Err( "Room graph has no connected pair of rooms".to_string() );
+    }
+
+    // edges along the path from start to goal, each with the room on the start side
+    let (_hops, parent) = graph.search( start, |_e| true );
+    let mut path = Vec::<(usize, usize)>::new();
+    let mut current = goal;
+    while let Some((prev, edge)) = parent[current] {
+        path.push( (prev, edge) );
+        current = prev;
+    }
+    path.reverse();
+
+    // only bridges can hold locks: with any other edge locked, a loop around it would still lead to the goal
+    let bridges = Vec::from_iter( path.into_iter().filter( |(_room, edge)| {
+        let locked = &graph.edges[*edge];
+        let (hops, _parent) = graph.search( start, |e| !std::ptr::eq( e, locked ) );
+        hops[goal].is_none()
+    } ) );
+    if lock_count > 0 && bridges.is_empty() {
+        return Err( "Every path from start to goal runs through a loop, so no edge can be locked".to_string() );
+    }
+
+    let lock_count = lock_count.min( bridges.len() );
+    let mut locks = PosList::new();
+    for i in 0..lock_count {
+        let (room, edge) = bridges[ ( i + 1 ) * bridges.len() / ( lock_count + 1 ) ];
+        graph.edges[edge].kind = EdgeKind::Locked( i as i64 );
+        locks.push( lock_position( graph, room, edge ) );
+    }
+
+    let mut keys = PosList::new();
+    let mut before = vec![ false; graph.rooms.len() ];
+    for i in 0..lock_count {
+        let (hops, _parent) = graph.search( start, |e| match e.kind {
+            EdgeKind::Locked(id) => id < i as i64,
+            EdgeKind::Corridor => true,
+        } );
+        let reachable = Vec::from_iter( ( 0..graph.rooms.len() ).filter( |r| hops[*r].is_some() ) );
+
+        // prefer rooms that only just opened up, and not the start room itself
+        let mut candidates = Vec::from_iter( reachable.iter().cloned().filter( |r| !before[*r] && *r != start ) );
+        if candidates.is_empty() {
+            candidates = reachable.clone();
+        }
+        let room = candidates[ random.random_range( 0..candidates.len() ) ];
+        keys.push( graph.rooms[room].center );
+
+        for r in reachable {
+            before[r] = true;
+        }
+    }
+
+    Ok( Progression{ start, goal, keys, locks } )
+}
+
+// The edge's corridor cell closest to the center of the room it's entered from, outside of every room.
+fn lock_position( graph: &RoomGraph, room: usize, edge: usize ) -> (i64, i64, i64) {
+    let center = graph.rooms[room].center;
+    let e = &graph.edges[edge];
+
+    let mut cells = Vec::from_iter( e.cells.iter().filter( |p| !graph.rooms[e.a].members.contains( *p ) && !graph.rooms[e.b].members.contains( *p ) ).cloned() );
+    cells.sort();
+
+    let dist = | p: &(i64, i64, i64) | ( p.0 - center.0 ).pow(2) + ( p.1 - center.1 ).pow(2) + ( p.2 - center.2 ).pow(2);
+    match cells.iter().min_by_key( |p| dist( p ) ) {
+        Some(p) => *p,
+        None => {
+            let other = graph.rooms[ if e.a == room { e.b } else { e.a } ].center;
+            ( ( center.0 + other.0 ) / 2, ( center.1 + other.1 ) / 2, ( center.2 + other.2 ) / 2 )
+        },
+    }
+}
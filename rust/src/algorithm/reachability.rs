@@ -0,0 +1,87 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::datagrid::{Selection, PosList, in_grid};
+
+
+
+// A player occupying a column of height cells, standing on top of a solid cell.
+pub struct Capsule<'a> {
+    pub solid: &'a Selection,
+    pub size: (usize, usize, usize),
+    pub height: i64,
+    pub max_step: i64,
+    pub max_drop: i64,
+}
+
+impl Capsule<'_> {
+    // Flood fills standing positions from the start points (each dropped to the floor below it), returning the reached
+    // positions and every other standing position in the grid.
+    pub fn reachability( &self, starts: &PosList ) -> (Selection, Selection) {
+        let mut reached = Box::new( HashSet::<(i64, i64, i64)>::new() );
+        let mut queue = VecDeque::<(i64, i64, i64)>::new();
+
+        for start in starts {
+            if let Some(floor) = self.drop_to_floor( *start ) && reached.insert( floor ) {
+                queue.push_back( floor );
+            }
+        }
+
+        while let Some(pos) = queue.pop_front() {
+            for (dx, dz) in [ (1, 0), (-1, 0), (0, 1), (0, -1) ] {
+                for dy in -self.max_drop..=self.max_step {
+                    let next = ( pos.0 + dx, pos.1 + dy, pos.2 + dz );
+                    if reached.contains( &next ) || !self.can_stand( next ) {
+                        continue;
+                    }
+                    // climbing needs headroom above the current spot, dropping needs a clear fall in the target column
+                    let clear = if dy > 0 {
+                        self.column_clear( pos, pos.1 + dy + self.height - 1 )
+                    } else {
+                        self.column_clear( next, pos.1 + self.height - 1 )
+                    };
+                    if clear {
+                        reached.insert( next );
+                        queue.push_back( next );
+                    }
+                }
+            }
+        }
+
+        let mut unreached = Box::new( HashSet::<(i64, i64, i64)>::new() );
+        for x in 0..self.size.0 as i64 {
+            for y in 0..self.size.1 as i64 {
+                for z in 0..self.size.2 as i64 {
+                    if self.can_stand( (x, y, z) ) && !reached.contains( &(x, y, z) ) {
+                        unreached.insert( (x, y, z) );
+                    }
+                }
+            }
+        }
+
+        (reached, unreached)
+    }
+
+    fn can_stand( &self, pos: (i64, i64, i64) ) -> bool {
+        pos.1 >= 1 && in_grid( self.size, pos ).is_some() &&
+            self.solid.contains( &( pos.0, pos.1 - 1, pos.2 ) ) && self.column_clear( pos, pos.1 + self.height - 1 )
+    }
+
+    // Whether the column at pos is free of solid cells from pos up to top; cells above the grid count as free.
+    fn column_clear( &self, pos: (i64, i64, i64), top: i64 ) -> bool {
+        ( pos.1..=top ).all( |y| !self.solid.contains( &( pos.0, y, pos.2 ) ) )
+    }
+
+    fn drop_to_floor( &self, pos: (i64, i64, i64) ) -> Option<(i64, i64, i64)> {
+        let mut y = pos.1.min( self.size.1 as i64 - 1 );
+        while y >= 1 {
+            if self.solid.contains( &( pos.0, y, pos.2 ) ) {
+                return None;
+            }
+            if self.can_stand( ( pos.0, y, pos.2 ) ) {
+                return Some( ( pos.0, y, pos.2 ) );
+            }
+            y -= 1;
+        }
+        None
+    }
+}
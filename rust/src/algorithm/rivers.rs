@@ -0,0 +1,159 @@
+use std::collections::{BinaryHeap, HashSet};
+
+use ndarray::Array2;
+
+use crate::datagrid::{Selection, PosList};
+use crate::algorithm::HeapEntry;
+
+
+
+const NEIGHBORS: [(i64, i64); 8] = [ (1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (1, -1), (-1, 1), (-1, -1) ];
+
+pub struct Rivers {
+    pub count: usize,
+    pub spacing: f64,
+    pub width: f64,
+    pub width_per_flow: f64,
+    pub depth: i64,
+    pub height_scale: f64,
+    pub height_offset: f64,
+}
+
+pub struct RiverResult {
+    pub carved: Selection,
+    pub paths: Vec<PosList>,
+    pub flow: Array2<f64>,
+}
+
+impl Rivers {
+    // Fills pits so every cell drains to the edge, routes flow by steepest descent, then traces rivers from the highest
+    // well-spaced cells. Heights are turned into cell Y the same way the Heightmap command does.
+    pub fn generate( &self, heights: &Array2<f64>, size_y: usize ) -> RiverResult {
+        let dim = heights.dim();
+        let filled = Self::fill_pits( heights );
+
+        // downstream neighbor of every cell, None where water leaves the map
+        let downstream = Array2::from_shape_fn( dim, |(x, z)| {
+            let mut best : Option<(usize, usize)> = None;
+            let mut best_slope = 0.0;
+            for (dx, dz) in NEIGHBORS {
+                let (nx, nz) = ( x as i64 + dx, z as i64 + dz );
+                if nx < 0 || nz < 0 || nx >= dim.0 as i64 || nz >= dim.1 as i64 {
+                    continue;
+                }
+                let slope = ( filled[[ x, z ]] - filled[[ nx as usize, nz as usize ]] ) / ( ( dx * dx + dz * dz ) as f64 ).sqrt();
+                if slope > best_slope {
+                    best_slope = slope;
+                    best = Some( ( nx as usize, nz as usize ) );
+                }
+            }
+            best
+        } );
+
+        let mut order = Vec::from_iter( filled.indexed_iter().map( |(pos, _h)| pos ) );
+        order.sort_by( |a, b| filled[*b].total_cmp( &filled[*a] ) );
+
+        let mut flow = Array2::<f64>::ones( dim );
+        for pos in order.iter() {
+            if let Some(next) = downstream[*pos] {
+                let passed = flow[*pos];
+                flow[next] += passed;
+            }
+        }
+
+        let mut by_height = Vec::from_iter( heights.indexed_iter().map( |(pos, _h)| pos ) );
+        by_height.sort_by( |a, b| heights[*b].total_cmp( &heights[*a] ).then( a.cmp( b ) ) );
+
+        let mut sources = Vec::<(usize, usize)>::new();
+        for pos in by_height {
+            if sources.len() >= self.count {
+                break;
+            }
+            let far = sources.iter().all( |s| {
+                ( ( s.0 as f64 - pos.0 as f64 ).powi(2) + ( s.1 as f64 - pos.1 as f64 ).powi(2) ).sqrt() >= self.spacing
+            } );
+            if far {
+                sources.push( pos );
+            }
+        }
+
+        let mut carved = Box::new( HashSet::<(i64, i64, i64)>::new() );
+        let mut visited = HashSet::<(usize, usize)>::new();
+        let mut paths = Vec::<PosList>::new();
+
+        for source in sources {
+            let mut path = PosList::new();
+            let mut current = Some( source );
+
+            while let Some(pos) = current {
+                let surface = self.surface( heights[pos], size_y );
+                path.push( ( pos.0 as i64, surface, pos.1 as i64 ) );
+                self.carve( pos, surface, flow[pos], dim, &mut carved );
+
+                // joining an earlier river ends this one
+                if !visited.insert( pos ) {
+                    break;
+                }
+                current = downstream[pos];
+            }
+
+            paths.push( path );
+        }
+
+        RiverResult{ carved, paths, flow }
+    }
+
+    // Priority-flood pit filling, raising each cell at least a tiny step above the cell it drains into.
+    fn fill_pits( heights: &Array2<f64> ) -> Array2<f64> {
+        let dim = heights.dim();
+        let mut filled = heights.clone();
+        let mut done = Array2::<bool>::from_elem( dim, false );
+        let mut open = BinaryHeap::<HeapEntry<(usize, usize)>>::new();
+
+        for ((x, z), h) in heights.indexed_iter() {
+            if x == 0 || z == 0 || x == dim.0 - 1 || z == dim.1 - 1 {
+                done[[ x, z ]] = true;
+                open.push( HeapEntry{ cost: *h, item: (x, z) } );
+            }
+        }
+
+        while let Some(entry) = open.pop() {
+            let (x, z) = entry.item;
+            for (dx, dz) in NEIGHBORS {
+                let (nx, nz) = ( x as i64 + dx, z as i64 + dz );
+                if nx < 0 || nz < 0 || nx >= dim.0 as i64 || nz >= dim.1 as i64 {
+                    continue;
+                }
+                let npos = ( nx as usize, nz as usize );
+                if done[npos] {
+                    continue;
+                }
+                done[npos] = true;
+                filled[npos] = filled[npos].max( filled[[ x, z ]] + 1e-6 );
+                open.push( HeapEntry{ cost: filled[npos], item: npos } );
+            }
+        }
+
+        filled
+    }
+
+    fn surface( &self, height: f64, size_y: usize ) -> i64 {
+        ( self.height_offset + self.height_scale * height ).floor().clamp( 0.0, size_y as f64 - 1.0 ) as i64
+    }
+
+    fn carve( &self, pos: (usize, usize), surface: i64, flow: f64, dim: (usize, usize), carved: &mut Selection ) {
+        let radius = self.width + self.width_per_flow * flow.sqrt();
+        let reach = radius.ceil() as i64;
+        for dx in -reach..=reach {
+            for dz in -reach..=reach {
+                let (x, z) = ( pos.0 as i64 + dx, pos.1 as i64 + dz );
+                if x < 0 || z < 0 || x >= dim.0 as i64 || z >= dim.1 as i64 || ( ( dx * dx + dz * dz ) as f64 ).sqrt() > radius {
+                    continue;
+                }
+                for y in ( surface - self.depth + 1 ).max(0)..=surface {
+                    carved.insert( (x, y, z) );
+                }
+            }
+        }
+    }
+}
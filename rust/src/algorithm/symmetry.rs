@@ -0,0 +1,148 @@
+use std::collections::HashSet;
+
+use crate::datagrid::{GridElement, PosList};
+use crate::resource::SymmetryMode;
+use crate::algorithm::orientation;
+
+
+
+// One element of a symmetry group: a number of quarter turns around Y, or a mirror along X and/or Z.
+#[derive(Clone, Copy)]
+struct SymOp {
+    turns: i64,
+    mirror_x: bool,
+    mirror_z: bool,
+}
+
+// Copies a source region of the grid onto the rest of it, so every cell matches its images under the group.
+pub struct Symmetry {
+    pub mode: SymmetryMode,
+    pub size: (usize, usize, usize),
+}
+
+impl Symmetry {
+    pub fn validate( &self ) -> Result<(), String> {
+        if self.mode == SymmetryMode::Rotate4 && self.size.0 != self.size.2 {
+            return Err( format!( "4-fold rotation needs a square grid, got {}x{}", self.size.0, self.size.2 ) );
+        }
+        Ok(())
+    }
+
+    fn ops( &self ) -> Vec<SymOp> {
+        let op = | turns, mirror_x, mirror_z | SymOp{ turns, mirror_x, mirror_z };
+        match self.mode {
+            SymmetryMode::MirrorX => vec![ op( 0, false, false ), op( 0, true, false ) ],
+            SymmetryMode::MirrorZ => vec![ op( 0, false, false ), op( 0, false, true ) ],
+            SymmetryMode::MirrorXZ => vec![ op( 0, false, false ), op( 0, true, false ), op( 0, false, true ), op( 0, true, true ) ],
+            SymmetryMode::Rotate2 => vec![ op( 0, false, false ), op( 2, false, false ) ],
+            SymmetryMode::Rotate4 => vec![ op( 0, false, false ), op( 1, false, false ), op( 2, false, false ), op( 3, false, false ) ],
+        }
+    }
+
+    // The region that gets copied; its images under the group cover the grid without overlapping except at fixed cells.
+    fn in_source( &self, p: (i64, i64, i64) ) -> bool {
+        let (sx, sz) = ( self.size.0 as i64, self.size.2 as i64 );
+        match self.mode {
+            SymmetryMode::MirrorX => p.0 < ( sx + 1 ) / 2,
+            SymmetryMode::MirrorZ => p.2 < ( sz + 1 ) / 2,
+            SymmetryMode::MirrorXZ => p.0 < ( sx + 1 ) / 2 && p.2 < ( sz + 1 ) / 2,
+            SymmetryMode::Rotate2 => p.0 < sx / 2 || ( sx % 2 == 1 && p.0 == sx / 2 && p.2 < ( sz + 1 ) / 2 ),
+            SymmetryMode::Rotate4 => p.0 < ( sx + 1 ) / 2 && p.2 < sx / 2,
+        }
+    }
+
+    fn map( &self, op: SymOp, p: (i64, i64, i64) ) -> (i64, i64, i64) {
+        let (sx, sz) = ( self.size.0 as i64, self.size.2 as i64 );
+        let (mut x, y, mut z) = p;
+        if op.mirror_x {
+            x = sx - 1 - x;
+        }
+        if op.mirror_z {
+            z = sz - 1 - z;
+        }
+        match op.turns {
+            1 => ( z, y, sx - 1 - x ),
+            2 => ( sx - 1 - x, y, sz - 1 - z ),
+            3 => ( sz - 1 - z, y, x ),
+            _ => ( x, y, z ),
+        }
+    }
+
+    fn inverse( op: SymOp ) -> SymOp {
+        SymOp{ turns: ( 4 - op.turns ) % 4, ..op }
+    }
+
+    fn orient( op: SymOp, value: i64 ) -> i64 {
+        let mut v = value;
+        if op.mirror_x {
+            v = orientation::mirror( v, 0 );
+        }
+        if op.mirror_z {
+            v = orientation::mirror( v, 2 );
+        }
+        orientation::rotate_y( v, op.turns )
+    }
+
+    // The source cell a cell copies from, and the operation taking it there. None for cells no image reaches.
+    fn source_of( &self, p: (i64, i64, i64) ) -> Option<((i64, i64, i64), SymOp)> {
+        self.ops().into_iter()
+            .map( |op| ( self.map( Self::inverse( op ), p ), op ) )
+            .find( |(s, _)| self.in_source( *s ) )
+    }
+
+    pub fn apply( &self, element: GridElement, is_rotation: bool ) -> Result<GridElement, String> {
+        match element {
+            GridElement::Int( arr ) => {
+                let mut out = arr.clone();
+                for ((x, y, z), v) in out.indexed_iter_mut() {
+                    if let Some((s, op)) = self.source_of( ( x as i64, y as i64, z as i64 ) ) {
+                        let from = arr[[ s.0 as usize, s.1 as usize, s.2 as usize ]];
+                        *v = if is_rotation { Self::orient( op, from ) } else { from };
+                    }
+                }
+                Ok( GridElement::Int( out ) )
+            },
+            GridElement::Float( arr ) => {
+                let mut out = arr.clone();
+                for ((x, y, z), v) in out.indexed_iter_mut() {
+                    if let Some((s, _)) = self.source_of( ( x as i64, y as i64, z as i64 ) ) {
+                        *v = arr[[ s.0 as usize, s.1 as usize, s.2 as usize ]];
+                    }
+                }
+                Ok( GridElement::Float( out ) )
+            },
+            GridElement::Sel( sel ) => {
+                let mut out = Box::new( HashSet::<(i64, i64, i64)>::new() );
+                for x in 0..self.size.0 as i64 {
+                    for y in 0..self.size.1 as i64 {
+                        for z in 0..self.size.2 as i64 {
+                            let from = self.source_of( (x, y, z) ).map( |(s, _)| s ).unwrap_or( (x, y, z) );
+                            if sel.contains( &from ) {
+                                out.insert( (x, y, z) );
+                            }
+                        }
+                    }
+                }
+                Ok( GridElement::Sel( out ) )
+            },
+            GridElement::List( ls ) => {
+                // points in the source region are duplicated, points only reached as images are replaced by them
+                let mut out : PosList = vec![];
+                for p in ls.iter() {
+                    if self.in_source( *p ) {
+                        for op in self.ops() {
+                            let image = self.map( op, *p );
+                            if !out.contains( &image ) {
+                                out.push( image );
+                            }
+                        }
+                    } else if self.source_of( *p ).is_none() {
+                        out.push( *p );
+                    }
+                }
+                Ok( GridElement::List( out ) )
+            },
+            _ => Err( "rooms and graphs can't be symmetrized".to_string() ),
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use std::collections::HashSet;
+
+use ndarray::Array3;
+
+use crate::datagrid::{DataGrid, GridElement, Room, Selection, in_grid};
+use crate::algorithm::orientation;
+
+
+
+// Moves every field of a data grid into a grid of a new size, dropping whatever lands outside it. Int fields named
+// 'rotation' have their GridMap orientations turned or mirrored along with the cells. New cells get the fill value,
+// except in 'mesh' (left empty with -1) and 'rotation' (left unrotated with 0).
+pub struct GridTransform {
+    pub new_size: (usize, usize, usize),
+    map: Box<dyn Fn( (i64, i64, i64) ) -> (i64, i64, i64)>,
+    turns: i64,
+    mirror_axis: Option<usize>,
+}
+
+impl GridTransform {
+    pub fn translate( size: (usize, usize, usize), by: (i64, i64, i64) ) -> Self {
+        Self::shifted( size, by )
+    }
+
+    // Quarter turns around Y, each taking +X towards -Z, matching GridMap orientation turns.
+    pub fn rotate_y( size: (usize, usize, usize), turns: i64 ) -> Self {
+        let turns = turns.rem_euclid(4);
+        let (sx, sz) = ( size.0 as i64, size.2 as i64 );
+        let new_size = if turns % 2 == 0 { size } else { ( size.2, size.1, size.0 ) };
+        let map : Box<dyn Fn( (i64, i64, i64) ) -> (i64, i64, i64)> = match turns {
+            0 => Box::new( |p| p ),
+            1 => Box::new( move |p| ( p.2, p.1, sx - 1 - p.0 ) ),
+            2 => Box::new( move |p| ( sx - 1 - p.0, p.1, sz - 1 - p.2 ) ),
+            _ => Box::new( move |p| ( sz - 1 - p.2, p.1, p.0 ) ),
+        };
+        Self{ new_size, map, turns, mirror_axis: None }
+    }
+
+    pub fn mirror( size: (usize, usize, usize), axis: usize ) -> Self {
+        let extent = [ size.0 as i64, size.1 as i64, size.2 as i64 ][axis];
+        let map = Box::new( move |p: (i64, i64, i64)| {
+            let mut c = [ p.0, p.1, p.2 ];
+            c[axis] = extent - 1 - c[axis];
+            ( c[0], c[1], c[2] )
+        } );
+        Self{ new_size: size, map, turns: 0, mirror_axis: Some(axis) }
+    }
+
+    // Keeps the cells from min (inclusive) to max (exclusive).
+    pub fn crop( min: (i64, i64, i64), max: (i64, i64, i64) ) -> Self {
+        let new_size = ( ( max.0 - min.0 ) as usize, ( max.1 - min.1 ) as usize, ( max.2 - min.2 ) as usize );
+        Self::shifted( new_size, ( -min.0, -min.1, -min.2 ) )
+    }
+
+    pub fn pad( size: (usize, usize, usize), before: (i64, i64, i64), after: (i64, i64, i64) ) -> Self {
+        let new_size = ( ( size.0 as i64 + before.0 + after.0 ) as usize, ( size.1 as i64 + before.1 + after.1 ) as usize, ( size.2 as i64 + before.2 + after.2 ) as usize );
+        Self::shifted( new_size, before )
+    }
+
+    // Changes the size while keeping cells where they are, growing or shrinking away from the origin.
+    pub fn resize( new_size: (usize, usize, usize) ) -> Self {
+        Self::shifted( new_size, (0, 0, 0) )
+    }
+
+    fn shifted( new_size: (usize, usize, usize), by: (i64, i64, i64) ) -> Self {
+        Self{ new_size, map: Box::new( move |p| ( p.0 + by.0, p.1 + by.1, p.2 + by.2 ) ), turns: 0, mirror_axis: None }
+    }
+
+    pub fn apply( &self, grid: DataGrid, fill: f64 ) -> DataGrid {
+        let mut out = DataGrid::sized( self.new_size );
+        out.stats = grid.stats;
+        out.failed_assertions = grid.failed_assertions;
+
+        for (name, ge) in grid.elements {
+            let moved = match ge {
+                GridElement::Int( arr ) => {
+                    let empty = match name.as_str() {
+                        "mesh" => -1,
+                        "rotation" => 0,
+                        _ => fill as i64,
+                    };
+                    let mut new_arr = Array3::<i64>::from_elem( self.new_size, empty );
+                    for ((x, y, z), v) in arr.indexed_iter() {
+                        if let Some(idx) = self.target( ( x as i64, y as i64, z as i64 ) ) {
+                            new_arr[idx] = if name == "rotation" { self.orient( *v ) } else { *v };
+                        }
+                    }
+                    GridElement::Int( new_arr )
+                },
+                GridElement::Float( arr ) => {
+                    let mut new_arr = Array3::<f64>::from_elem( self.new_size, fill );
+                    for ((x, y, z), v) in arr.indexed_iter() {
+                        if let Some(idx) = self.target( ( x as i64, y as i64, z as i64 ) ) {
+                            new_arr[idx] = *v;
+                        }
+                    }
+                    GridElement::Float( new_arr )
+                },
+                GridElement::Sel( sel ) => GridElement::Sel( self.move_sel( &sel ) ),
+                GridElement::List( ls ) => GridElement::List( ls.into_iter().filter_map( |p| self.target_pos( p ) ).collect() ),
+                GridElement::Rooms( rms ) => GridElement::Rooms( rms.iter().map( |r| self.move_room( r ) ).collect() ),
+                GridElement::Graph( mut graph ) => {
+                    graph.rooms = graph.rooms.iter().map( |r| self.move_room( r ) ).collect();
+                    for e in graph.edges.iter_mut() {
+                        e.cells = self.move_sel( &e.cells );
+                    }
+                    GridElement::Graph( graph )
+                },
+            };
+            out.elements.insert( name, moved );
+        }
+
+        out
+    }
+
+    fn target_pos( &self, pos: (i64, i64, i64) ) -> Option<(i64, i64, i64)> {
+        let p = ( self.map )( pos );
+        in_grid( self.new_size, p ).map( |_| p )
+    }
+
+    fn target( &self, pos: (i64, i64, i64) ) -> Option<[usize; 3]> {
+        in_grid( self.new_size, ( self.map )( pos ) )
+    }
+
+    fn move_sel( &self, sel: &Selection ) -> Selection {
+        Box::new( HashSet::from_iter( sel.iter().filter_map( |p| self.target_pos( *p ) ) ) )
+    }
+
+    // Rooms keep their index even when cropped away entirely; their centers are clamped into the new grid.
+    fn move_room( &self, room: &Room ) -> Room {
+        let c = ( self.map )( room.center );
+        let center = (
+            c.0.clamp( 0, self.new_size.0 as i64 - 1 ),
+            c.1.clamp( 0, self.new_size.1 as i64 - 1 ),
+            c.2.clamp( 0, self.new_size.2 as i64 - 1 ),
+        );
+        Room{ members: self.move_sel( &room.members ), center }
+    }
+
+    fn orient( &self, value: i64 ) -> i64 {
+        match self.mirror_axis {
+            Some(axis) => orientation::mirror( value, axis ),
+            None => orientation::rotate_y( value, self.turns ),
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::HashSet;
+
+use godot::prelude::*;
+use godot::classes::{Shape3D, BoxShape3D, SphereShape3D, CapsuleShape3D, CylinderShape3D};
+
+use crate::datagrid::{Selection, in_grid};
+
+
+
+// Triangle corners in fractional cell coordinates, where cell (x, y, z) spans x..x+1 on each axis.
+pub type Triangle = [(f64, f64, f64); 3];
+
+// Cells touched by the triangles, found by sampling each one at under half a cell's spacing.
+pub fn surface( tris: &[Triangle], size: (usize, usize, usize) ) -> Selection {
+    let mut out = Box::new( HashSet::<(i64, i64, i64)>::new() );
+    let dist = | a: (f64, f64, f64), b: (f64, f64, f64) | ( ( a.0 - b.0 ).powi(2) + ( a.1 - b.1 ).powi(2) + ( a.2 - b.2 ).powi(2) ).sqrt();
+
+    for [a, b, c] in tris.iter() {
+        let longest = dist( *a, *b ).max( dist( *b, *c ) ).max( dist( *c, *a ) );
+        let n = ( longest * 2.0 ).ceil().max(1.0) as usize;
+        for i in 0..=n {
+            for j in 0..=( n - i ) {
+                let (u, v) = ( i as f64 / n as f64, j as f64 / n as f64 );
+                let w = 1.0 - u - v;
+                let p = (
+                    ( a.0 * w + b.0 * u + c.0 * v ).floor() as i64,
+                    ( a.1 * w + b.1 * u + c.1 * v ).floor() as i64,
+                    ( a.2 * w + b.2 * u + c.2 * v ).floor() as i64,
+                );
+                if in_grid( size, p ).is_some() {
+                    out.insert( p );
+                }
+            }
+        }
+    }
+
+    out
+}
+
+// The surface plus every cell whose center is enclosed, counting crossings of a vertical ray through each column.
+// The mesh should be closed; the ray is nudged off cell centers so it doesn't run along shared triangle edges.
+pub fn solid( tris: &[Triangle], size: (usize, usize, usize) ) -> Selection {
+    let mut out = surface( tris, size );
+
+    for x in 0..size.0 as i64 {
+        for z in 0..size.2 as i64 {
+            let (px, pz) = ( x as f64 + 0.5 + 1.3e-7, z as f64 + 0.5 + 0.7e-7 );
+            let mut hits : Vec<f64> = tris.iter().filter_map( |t| vertical_hit( t, px, pz ) ).collect();
+            hits.sort_by( |a, b| a.total_cmp( b ) );
+
+            for pair in hits.chunks_exact(2) {
+                let from = ( pair[0] - 0.5 ).ceil().max(0.0) as i64;
+                let to = ( pair[1] - 0.5 ).floor().min( size.1 as f64 - 1.0 ) as i64;
+                for y in from..=to {
+                    out.insert( (x, y, z) );
+                }
+            }
+        }
+    }
+
+    out
+}
+
+// Height where a vertical line at (px, pz) crosses the triangle, if it does.
+fn vertical_hit( t: &Triangle, px: f64, pz: f64 ) -> Option<f64> {
+    let [a, b, c] = t;
+    let det = ( b.0 - a.0 ) * ( c.2 - a.2 ) - ( c.0 - a.0 ) * ( b.2 - a.2 );
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    let u = ( ( px - a.0 ) * ( c.2 - a.2 ) - ( c.0 - a.0 ) * ( pz - a.2 ) ) / det;
+    let v = ( ( b.0 - a.0 ) * ( pz - a.2 ) - ( px - a.0 ) * ( b.2 - a.2 ) ) / det;
+    if u < 0.0 || v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    Some( a.1 + ( b.1 - a.1 ) * u + ( c.1 - a.1 ) * v )
+}
+
+// Cells whose centers pass the test, given in fractional cell coordinates.
+pub fn from_predicate( size: (usize, usize, usize), inside: impl Fn( (f64, f64, f64) ) -> bool ) -> Selection {
+    let mut out = Box::new( HashSet::<(i64, i64, i64)>::new() );
+    for x in 0..size.0 as i64 {
+        for y in 0..size.1 as i64 {
+            for z in 0..size.2 as i64 {
+                if inside( ( x as f64 + 0.5, y as f64 + 0.5, z as f64 + 0.5 ) ) {
+                    out.insert( (x, y, z) );
+                }
+            }
+        }
+    }
+    out
+}
+
+// Cells of the selection with a face neighbour outside it.
+pub fn shell( sel: &Selection ) -> Selection {
+    let faces = [ (1, 0, 0), (-1, 0, 0), (0, 1, 0), (0, -1, 0), (0, 0, 1), (0, 0, -1) ];
+    Box::new( sel.iter()
+        .filter( |p| faces.iter().any( |d| !sel.contains( &( p.0 + d.0, p.1 + d.1, p.2 + d.2 ) ) ) )
+        .copied()
+        .collect() )
+}
+
+// Inside test in the shape's own space for the primitive shapes that have one.
+pub fn primitive_inside( shape: &Gd<Shape3D> ) -> Option<Box<dyn Fn( Vector3 ) -> bool>> {
+    if let Ok(bx) = shape.clone().try_cast::<BoxShape3D>() {
+        let half = bx.get_size() / 2.0;
+        return Some( Box::new( move |p: Vector3| p.x.abs() <= half.x && p.y.abs() <= half.y && p.z.abs() <= half.z ) );
+    }
+    if let Ok(sphere) = shape.clone().try_cast::<SphereShape3D>() {
+        let r = sphere.get_radius();
+        return Some( Box::new( move |p: Vector3| p.length() <= r ) );
+    }
+    if let Ok(capsule) = shape.clone().try_cast::<CapsuleShape3D>() {
+        // the height includes both hemispherical caps
+        let r = capsule.get_radius();
+        let half = ( capsule.get_height() / 2.0 - r ).max(0.0);
+        return Some( Box::new( move |p: Vector3| ( p - Vector3::new( 0.0, p.y.clamp( -half, half ), 0.0 ) ).length() <= r ) );
+    }
+    if let Ok(cylinder) = shape.clone().try_cast::<CylinderShape3D>() {
+        let (r, half) = ( cylinder.get_radius(), cylinder.get_height() / 2.0 );
+        return Some( Box::new( move |p: Vector3| p.y.abs() <= half && Vector2::new( p.x, p.z ).length() <= r ) );
+    }
+    None
+}
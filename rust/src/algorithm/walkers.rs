@@ -0,0 +1,77 @@
+use std::collections::HashSet;
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+
+use crate::datagrid::{Selection, PosList, in_grid};
+
+
+
+pub struct RandomWalkers {
+    pub count: usize,
+    pub lifetime: usize,
+    pub turn_chance: f64,
+    pub vertical_chance: f64,
+    pub radius: i64,
+}
+
+impl RandomWalkers {
+    // Walkers are handed out to the start points in turn and carve a cube of the given radius around every cell they visit.
+    pub fn walk( &self, starts: &PosList, size: (usize, usize, usize), seed: i64 ) -> Result<Selection, String> {
+        if starts.is_empty() {
+            return Err( "Random walkers need at least one start point".to_string() );
+        }
+
+        let mut random = ChaCha12Rng::seed_from_u64( seed as u64 );
+        let mut carved = Box::new( HashSet::<(i64, i64, i64)>::new() );
+
+        for widx in 0..self.count {
+            let mut pos = starts[ widx % starts.len() ];
+            let mut dir = self.pick_direction( &mut random );
+
+            for _step in 0..self.lifetime {
+                self.carve( pos, size, &mut carved );
+
+                if random.random_bool( self.turn_chance.clamp( 0.0, 1.0 ) ) {
+                    dir = self.pick_direction( &mut random );
+                }
+
+                let next = ( pos.0 + dir.0, pos.1 + dir.1, pos.2 + dir.2 );
+                if in_grid( size, next ).is_some() {
+                    pos = next;
+                } else {
+                    // bounce off the grid edge by turning instead of stepping
+                    dir = self.pick_direction( &mut random );
+                }
+            }
+
+            self.carve( pos, size, &mut carved );
+        }
+
+        Ok( carved )
+    }
+
+    fn pick_direction( &self, random: &mut ChaCha12Rng ) -> (i64, i64, i64) {
+        let sign = if random.random_bool( 0.5 ) { 1 } else { -1 };
+        if random.random_bool( self.vertical_chance.clamp( 0.0, 1.0 ) ) {
+            ( 0, sign, 0 )
+        } else if random.random_bool( 0.5 ) {
+            ( sign, 0, 0 )
+        } else {
+            ( 0, 0, sign )
+        }
+    }
+
+    fn carve( &self, pos: (i64, i64, i64), size: (usize, usize, usize), carved: &mut Selection ) {
+        for dx in -self.radius..=self.radius {
+            for dy in -self.radius..=self.radius {
+                for dz in -self.radius..=self.radius {
+                    let cell = ( pos.0 + dx, pos.1 + dy, pos.2 + dz );
+                    if in_grid( size, cell ).is_some() {
+                        carved.insert( cell );
+                    }
+                }
+            }
+        }
+    }
+}
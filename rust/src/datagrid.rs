@@ -1,4 +1,4 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 
 use godot::prelude::*;
 use godot::classes::Expression;
@@ -13,6 +13,8 @@ use crate::resource::{Neighborhood, EdgeMode};
 
 pub type Selection = Box<HashSet<(i64, i64, i64)>>;
 pub type PosList = Vec<(i64, i64, i64)>;
+// Hop count to every room and the (room, edge) each was reached from, see RoomGraph::search.
+pub type RoomSearch = ( Vec<Option<usize>>, Vec<Option<(usize, usize)>> );
 
 
 pub enum GridElement {
@@ -21,6 +23,7 @@ pub enum GridElement {
     Sel( Selection ),
     List( PosList ),
     Rooms( Vec<Room> ),
+    Graph( RoomGraph ),
 }
 
 #[derive(GodotConvert, Var, Export, Default)]
@@ -32,14 +35,100 @@ pub enum ElemType {
     Bool
 }
 
+#[derive(Clone)]
 pub struct Room {
     pub members: Selection,
     pub center: (i64, i64, i64),
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    Corridor,
+    Locked( i64 ),
+}
+
+pub struct RoomEdge {
+    pub a: usize,
+    pub b: usize,
+    pub kind: EdgeKind,
+    pub cells: Selection,
+}
+
+// Rooms as nodes and the corridors joining them as edges, each edge keeping the cells carved for it.
+pub struct RoomGraph {
+    pub rooms: Vec<Room>,
+    pub edges: Vec<RoomEdge>,
+}
+
+impl RoomGraph {
+    pub fn neighbors( &self, room: usize ) -> Vec<usize> {
+        let mut out = Vec::from_iter( self.edges.iter().filter_map( |e| {
+            if e.a == room { Some(e.b) } else if e.b == room { Some(e.a) } else { None }
+        } ) );
+        out.sort();
+        out.dedup();
+        out
+    }
+
+    pub fn degree( &self, room: usize ) -> usize {
+        self.edges.iter().filter( |e| e.a == room || e.b == room ).count()
+    }
+
+    // Breadth-first search over edges the filter allows, returning the hop count to every room and the (room, edge) each was reached from.
+    pub fn search( &self, from: usize, passable: impl Fn( &RoomEdge ) -> bool ) -> RoomSearch {
+        let mut hops = vec![ None; self.rooms.len() ];
+        let mut parent = vec![ None; self.rooms.len() ];
+        let mut queue = VecDeque::<usize>::new();
+
+        if from >= self.rooms.len() {
+            return (hops, parent);
+        }
+
+        hops[from] = Some(0);
+        queue.push_back( from );
+
+        while let Some(room) = queue.pop_front() {
+            for (eidx, e) in self.edges.iter().enumerate() {
+                let other = if e.a == room { e.b } else if e.b == room { e.a } else { continue; };
+                if hops[other].is_some() || !passable( e ) {
+                    continue;
+                }
+                hops[other] = Some( hops[room].unwrap() + 1 );
+                parent[other] = Some( (room, eidx) );
+                queue.push_back( other );
+            }
+        }
+
+        (hops, parent)
+    }
+
+    // Rooms along the shortest path in hops, including both ends, or None when they aren't connected.
+    pub fn hop_path( &self, from: usize, to: usize ) -> Option<Vec<usize>> {
+        let (hops, parent) = self.search( from, |_e| true );
+        hops.get( to ).copied().flatten()?;
+
+        let mut path = vec![ to ];
+        let mut current = to;
+        while let Some((prev, _edge)) = parent[current] {
+            path.push( prev );
+            current = prev;
+        }
+        path.reverse();
+        Some( path )
+    }
+
+    pub fn room_at( &self, pos: (i64, i64, i64) ) -> Option<usize> {
+        self.rooms.iter().position( |r| r.center == pos ).or_else( || self.rooms.iter().position( |r| r.members.contains( &pos ) ) )
+    }
+}
+
 pub struct DataGrid {
     pub size: (usize, usize, usize),
     pub elements: HashMap<String, GridElement>,
+    // single numbers measured by commands, like counts
+    pub stats: HashMap<String, f64>,
+    // messages from Assert commands that didn't hold
+    pub failed_assertions: Vec<String>,
 }
 
 impl DataGrid {
@@ -47,9 +136,35 @@ impl DataGrid {
         Self {
             size: sz,
             elements: HashMap::<String, GridElement>::new(),
+            stats: HashMap::<String, f64>::new(),
+            failed_assertions: Vec::<String>::new(),
         }
     }
 
+    // One number per field (the mean of Int/Float fields, the element count of the others) along with every stat and the grid size.
+    pub fn summary(&self) -> Dictionary {
+        let mut dict = Dictionary::new();
+        let cells = ( self.size.0 * self.size.1 * self.size.2 ).max(1) as f64;
+
+        for (k, v) in &self.elements {
+            let val = match v {
+                GridElement::Int( arr ) => arr.iter().sum::<i64>() as f64 / cells,
+                GridElement::Float( arr ) => arr.iter().sum::<f64>() / cells,
+                GridElement::Sel( select ) => select.len() as f64,
+                GridElement::List( ls ) => ls.len() as f64,
+                GridElement::Rooms( rms ) => rms.len() as f64,
+                GridElement::Graph( graph ) => graph.rooms.len() as f64,
+            };
+            let _ = dict.insert( GString::from(k), val );
+        }
+        for (k, v) in &self.stats {
+            let _ = dict.insert( GString::from(k), *v );
+        }
+        let _ = dict.insert( "size", Vector3i::new( self.size.0 as i32, self.size.1 as i32, self.size.2 as i32 ) );
+
+        dict
+    }
+
     pub fn parallel_expr(&mut self, name: String, expr: &GString, typ: &ElemType) -> Result<(), String> {
         let help = Helper::new_alloc();
 
@@ -131,6 +246,7 @@ impl DataGrid {
             GridElement::Float(_) => { new_ge = GridElement::Float( Array3::<f64>::zeros(self.size) ); },
             GridElement::Sel(_) => { is_bool = true; new_ge = GridElement::Sel( Box::new( HashSet::<(i64, i64, i64)>::new() ) ); },
             GridElement::Rooms(_) => { return Err( "SampleNeighborhood called on a room list field (incompatible).".to_string() ) },
+            GridElement::Graph(_) => { return Err( "SampleNeighborhood called on a room graph field (incompatible).".to_string() ) },
             GridElement::List(_) => { return Err( "SampleNeighborhood called on a position list field (incompatible, try ListToSel).".to_string() ) },
         }
 
@@ -228,47 +344,38 @@ impl DataGrid {
     }
 
     fn check_pos( &self, pos: (i32, i32, i32), mode: EdgeMode ) -> Option<[usize; 3]> {
+        wrap_pos( self.size, pos, mode )
+    }
+}
 
-        let newx : usize;
-        if pos.0 > 0 && pos.0 < self.size.0 as i32 {
-            newx = pos.0 as usize;
-        } else if mode == EdgeMode::Ignore {
-            return None;
-        } else if mode == EdgeMode::Loop {
-            newx = ( pos.0 % ( self.size.0 as i32 ) ) as usize;
-        } else if pos.0 < 0 {
-            newx = 0;
-        } else {
-            newx = self.size.0 - 1;
-        }
 
-        let newy : usize;
-        if pos.1 > 0 && pos.1 < self.size.1 as i32 {
-            newy = pos.1 as usize;
-        } else if mode == EdgeMode::Ignore {
-            return None;
-        } else if mode == EdgeMode::Loop {
-            newy = ( pos.1 % ( self.size.1 as i32 ) ) as usize;
-        } else if pos.1 < 0 {
-            newy = 0;
-        } else {
-            newy = self.size.1 - 1;
-        }
+pub fn wrap_pos( size: (usize, usize, usize), pos: (i32, i32, i32), mode: EdgeMode ) -> Option<[usize; 3]> {
+    let newx = wrap_axis( pos.0, size.0, mode )?;
+    let newy = wrap_axis( pos.1, size.1, mode )?;
+    let newz = wrap_axis( pos.2, size.2, mode )?;
 
-        let newz : usize;
-        if pos.2 > 0 && pos.2 < self.size.2 as i32 {
-            newz = pos.2 as usize;
-        } else if mode == EdgeMode::Ignore {
-            return None;
-        } else if mode == EdgeMode::Loop {
-            newz = ( pos.2 % ( self.size.2 as i32 ) ) as usize;
-        } else if pos.2 < 0 {
-            newz = 0;
-        } else {
-            newz = self.size.2 - 1;
-        }
+    Some( [newx, newy, newz] )
+}
+
+// Index of a selection or list position if it lies inside the grid, compared in i64 so far-off positions aren't truncated.
+pub fn in_grid( size: (usize, usize, usize), pos: (i64, i64, i64) ) -> Option<[usize; 3]> {
+    if pos.0 < 0 || pos.1 < 0 || pos.2 < 0 || pos.0 >= size.0 as i64 || pos.1 >= size.1 as i64 || pos.2 >= size.2 as i64 {
+        return None;
+    }
+    Some( [ pos.0 as usize, pos.1 as usize, pos.2 as usize ] )
+}
 
-        Some( [newx, newy, newz] )
+fn wrap_axis( p: i32, len: usize, mode: EdgeMode ) -> Option<usize> {
+    if p >= 0 && p < len as i32 {
+        Some( p as usize )
+    } else if mode == EdgeMode::Ignore {
+        None
+    } else if mode == EdgeMode::Loop {
+        Some( p.rem_euclid( len as i32 ) as usize )
+    } else if p < 0 {
+        Some( 0 )
+    } else {
+        Some( len - 1 )
     }
 }
 
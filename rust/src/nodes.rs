@@ -7,7 +7,39 @@ use godot::global::{godot_warn, godot_error};
 use ndarray::Array3;
 
 use crate::resource::{MapGenCommand, NeedsInput};
-use crate::datagrid::{DataGrid, GridElement};
+use crate::datagrid::{DataGrid, GridElement, RoomGraph, EdgeKind};
+
+
+// What commands reading the scene need to know about the GeneratedGridMap running them. Node paths resolve relative
+// to it, and cell positions are offset by where its results get placed.
+pub struct SceneContext {
+    pub grid_map: Gd<GridMap>,
+    pub placement_offset: Vector3i,
+}
+
+impl SceneContext {
+    pub fn resolve( &self, path: &NodePath ) -> Option<Gd<Node>> {
+        self.grid_map.get_node_or_null( path )
+    }
+
+    // Fractional cell coordinates of a world position, where cell (x, y, z) spans x..x+1 on each axis.
+    pub fn to_cell_space( &self, global: Vector3 ) -> (f64, f64, f64) {
+        let local = self.grid_map.to_local( global ) / self.grid_map.get_cell_size();
+        let o = self.placement_offset;
+        ( local.x as f64 - o.x as f64, local.y as f64 - o.y as f64, local.z as f64 - o.z as f64 )
+    }
+
+    pub fn from_cell_space( &self, cell: (f64, f64, f64) ) -> Vector3 {
+        let o = self.placement_offset;
+        let local = Vector3::new( ( cell.0 + o.x as f64 ) as f32, ( cell.1 + o.y as f64 ) as f32, ( cell.2 + o.z as f64 ) as f32 ) * self.grid_map.get_cell_size();
+        self.grid_map.to_global( local )
+    }
+
+    pub fn to_cell( &self, global: Vector3 ) -> (i64, i64, i64) {
+        let cell = self.grid_map.local_to_map( self.grid_map.to_local( global ) ) - self.placement_offset;
+        ( cell.x as i64, cell.y as i64, cell.z as i64 )
+    }
+}
 
 
 #[derive(GodotClass)]
@@ -18,6 +50,13 @@ pub struct GeneratedGridMap {
     pub editor_placement_offset: Vector3i,
     #[export]
     pub editor_seed: i64,
+    // how many seeds to try before giving up when Assert commands fail
+    #[export]
+    #[init(val = 1)]
+    pub max_attempts: i64,
+    // the seed the stored data grid was generated with
+    #[var(get)]
+    pub generated_seed: i64,
     pub result_grid: Option<DataGrid>,
 }
 
@@ -45,15 +84,53 @@ impl GeneratedGridMap {
         }
         let as_mgn = as_mgn.unwrap();
 
-        let gen_result = as_mgn.bind().generate( seed );
+        let attempts = self.max_attempts.max(1);
+        let scene = SceneContext{ grid_map: self.to_gd().upcast::<GridMap>(), placement_offset: self.editor_placement_offset };
+
+        // when no seed passes, the attempt with the fewest failed assertions is kept so there's still something to look at
+        let mut best : Option<(i64, DataGrid)> = None;
+        let mut last_error = String::new();
+
+        for attempt in 0..attempts {
+            let try_seed = seed.wrapping_add( attempt.wrapping_mul( 1_000_003 ) );
+            let gen_result = as_mgn.bind().generate( try_seed, &scene );
 
-        if gen_result.is_err() {
-            godot_error!("GeneratedGridMap encountered error:\n{}", gen_result.err().unwrap() );
+            // an erroring seed counts as a failed attempt, it's only reported as an error if no seed got through
+            let grid = match gen_result {
+                Ok(grid) => grid,
+                Err(e) => {
+                    if attempts > 1 {
+                        godot_warn!("GeneratedGridMap seed {} encountered error:\n{}", try_seed, e );
+                    }
+                    last_error = e;
+                    continue;
+                }
+            };
+
+            if grid.failed_assertions.is_empty() {
+                if attempt > 0 {
+                    godot_print!("GeneratedGridMap passed all assertions on attempt {} with seed {}.", attempt + 1, try_seed );
+                }
+                best = Some( (try_seed, grid) );
+                break;
+            }
+
+            godot_warn!("GeneratedGridMap seed {} failed assertions:\n{}", try_seed, grid.failed_assertions.join("\n") );
+            if best.as_ref().is_none_or( |(_s, g)| grid.failed_assertions.len() < g.failed_assertions.len() ) {
+                best = Some( (try_seed, grid) );
+            }
+        }
+
+        let Some((best_seed, grid)) = best else {
+            godot_error!("GeneratedGridMap encountered error:\n{}", last_error );
             self.result_grid = None;
             return;
+        };
+        if !grid.failed_assertions.is_empty() {
+            godot_warn!("GeneratedGridMap found no seed passing all assertions in {} attempts; keeping seed {}, which failed:\n{}", attempts, best_seed, grid.failed_assertions.join("\n") );
         }
-
-        self.result_grid = gen_result.ok();
+        self.generated_seed = best_seed;
+        self.result_grid = Some(grid);
         self.signals().generation_finished().emit();
     }
 
@@ -135,6 +212,14 @@ impl GeneratedGridMap {
                         }
                         return (-1).to_variant();
                     },
+                    GridElement::Graph(graph) => {
+                        for (idx, rm) in graph.rooms.iter().enumerate() {
+                            if rm.members.contains( &(position.x as i64, position.y as i64, position.z as i64) ) {
+                                return (idx as i64).to_variant();
+                            }
+                        }
+                        return (-1).to_variant();
+                    },
                     GridElement::List(vec) => {
                         for (idx, pos) in vec.into_iter().enumerate() {
                             if (position.x as i64, position.y as i64, position.z as i64) == *pos {
@@ -178,6 +263,103 @@ impl GeneratedGridMap {
         }
     }
 
+    #[func]
+    pub fn get_stat(&self, name: GString) -> f64 {
+        if let Some(ref rg) = self.result_grid {
+            if let Some(val) = rg.stats.get(&name.to_string()) {
+                return *val;
+            } else {
+                godot_error!("Attempt to get stat on GeneratedGridMap failed due to nonexistent stat name.");
+                return 0.0;
+            }
+        } else {
+            godot_error!("Attempt to get stat on GeneratedGridMap failed due to lack of successfully generated data grid.");
+            return 0.0;
+        }
+    }
+
+    #[func]
+    pub fn graph_room_count(&self, name: GString) -> i64 {
+        match self.get_graph( &name ) {
+            Some(graph) => graph.rooms.len() as i64,
+            None => 0,
+        }
+    }
+
+    #[func]
+    pub fn graph_room_center(&self, name: GString, room: i64) -> Vector3i {
+        if let Some(graph) = self.get_graph( &name ) {
+            if let Some(rm) = graph.rooms.get( room as usize ) {
+                return Vector3i::new( rm.center.0 as i32, rm.center.1 as i32, rm.center.2 as i32 );
+            }
+            godot_error!("Attempt to get room center on GeneratedGridMap failed due to room index out of range.");
+        }
+        Vector3i::ZERO
+    }
+
+    #[func]
+    pub fn graph_neighbors(&self, name: GString, room: i64) -> Array<i64> {
+        match self.get_graph( &name ) {
+            Some(graph) => Array::from( graph.neighbors( room as usize ).into_iter().map( |r| r as i64 ).collect::<Vec<_>>().as_slice() ),
+            None => Array::new(),
+        }
+    }
+
+    #[func]
+    pub fn graph_degree(&self, name: GString, room: i64) -> i64 {
+        match self.get_graph( &name ) {
+            Some(graph) => graph.degree( room as usize ) as i64,
+            None => 0,
+        }
+    }
+
+    // Room indices along the path with the fewest corridors, empty when the rooms aren't connected.
+    #[func]
+    pub fn graph_path(&self, name: GString, from: i64, to: i64) -> Array<i64> {
+        if let Some(graph) = self.get_graph( &name ) {
+            if let Some(path) = graph.hop_path( from as usize, to as usize ) {
+                return Array::from( path.into_iter().map( |r| r as i64 ).collect::<Vec<_>>().as_slice() );
+            }
+        }
+        Array::new()
+    }
+
+    // Each edge as a pair of room indices, in the same order the edges are stored.
+    #[func]
+    pub fn graph_edges(&self, name: GString) -> Array<Vector2i> {
+        match self.get_graph( &name ) {
+            Some(graph) => Array::from( graph.edges.iter().map( |e| Vector2i::new( e.a as i32, e.b as i32 ) ).collect::<Vec<_>>().as_slice() ),
+            None => Array::new(),
+        }
+    }
+
+    // Lock id of an edge placed by a LockAndKey command, or -1 for an open corridor.
+    #[func]
+    pub fn graph_edge_lock(&self, name: GString, edge: i64) -> i64 {
+        if let Some(graph) = self.get_graph( &name ) {
+            if let Some(e) = graph.edges.get( edge as usize ) {
+                if let EdgeKind::Locked(id) = e.kind {
+                    return id;
+                }
+            }
+        }
+        -1
+    }
+
+    fn get_graph(&self, name: &GString) -> Option<&RoomGraph> {
+        if let Some(ref rg) = self.result_grid {
+            if let Some(GridElement::Graph( graph )) = rg.elements.get(&name.to_string()) {
+                return Some(graph);
+            } else {
+                godot_error!("Attempt to query room graph on GeneratedGridMap failed due to missing or incorrect-typed graph.");
+                return None;
+            }
+        } else {
+            godot_error!("Attempt to query room graph on GeneratedGridMap failed due to lack of successfully generated data grid.");
+            return None;
+        }
+    }
+
 }
 
 
@@ -192,7 +374,7 @@ pub struct MapGenNode {
 
 #[godot_api]
 impl MapGenNode {
-    pub fn generate( &self, seed: i64 ) -> Result<DataGrid, String> {
+    pub fn generate( &self, seed: i64, scene: &SceneContext ) -> Result<DataGrid, String> {
         if self.command.is_none() {
             return Err( "No command resource set in a generation node!".to_string() );
         }
@@ -217,12 +399,12 @@ impl MapGenNode {
             }
             let as_mgn = as_mgn.unwrap();
 
-            let gen_result = as_mgn.bind().generate( seed );
+            let gen_result = as_mgn.bind().generate( seed, scene );
 
             if gen_result.is_err() {
                 return gen_result;
             } else {
-                return comm.bind().run_one( seed, gen_result.unwrap(), self.base().get_name().to_string() );
+                return comm.bind().run_one( seed, gen_result.unwrap(), self.base().get_name().to_string(), scene );
             }
         }
 
@@ -2,14 +2,28 @@ use std::collections::HashSet;
 use std::cmp::{Ord, Ordering};
 
 use godot::prelude::*;
-use godot::classes::FastNoiseLite;
+use godot::classes::{FastNoiseLite, Expression, GridMap, Node3D, Path3D, MeshInstance3D, CollisionShape3D, ConcavePolygonShape3D};
+use godot::builtin::array;
 
-use ndarray::Array;
+use ndarray::{Array, Array2, Array3, Axis};
 
-use crate::datagrid::{DataGrid, GridElement, ElemType, Selection};
+use crate::datagrid::{DataGrid, GridElement, ElemType, Selection, PosList, RoomGraph, RoomEdge, EdgeKind, Helper, in_grid};
 use crate::algorithm::{AlgorithmHelper, RectPrism};
-use crate::algorithm::pathcarver::SearchMap;
+use crate::algorithm::pathcarver::{SearchMap, Connector};
 use crate::algorithm::cellular_automata::CellAutoRule;
+use crate::algorithm::walkers::RandomWalkers;
+use crate::algorithm::erosion::Erosion;
+use crate::algorithm::fluid::FluidFill;
+use crate::algorithm::rivers::Rivers;
+use crate::algorithm::classify::Classifier;
+use crate::algorithm::progression;
+use crate::algorithm::distance::DistanceMap;
+use crate::algorithm::reachability::Capsule;
+use crate::algorithm::transform::GridTransform;
+use crate::algorithm::symmetry::Symmetry;
+use crate::algorithm::prefab::{Prefab, PrefabStamper, Anchor};
+use crate::algorithm::voxelize::{self, Triangle};
+use crate::nodes::{SceneContext, GeneratedGridMap};
 
 
 #[derive(GodotConvert, Var, Export, Default)]
@@ -33,6 +47,27 @@ pub enum CommandMode {
     CellularAutomata,
     IntervalSelect,
     SelectFall,
+    PoissonSample,
+    RandomWalkers,
+    Voronoi,
+    Heightmap,
+    Erosion,
+    FloodFill,
+    Rivers,
+    Classify,
+    FindDoors,
+    LockAndKey,
+    DijkstraMap,
+    Reachability,
+    EnsureConnected,
+    Assert,
+    Transform,
+    Symmetrize,
+    StampPrefabs,
+    ImportGridMap,
+    MarkerInput,
+    PathInput,
+    VoxelizeInput,
 }
 
 #[derive(GodotConvert, Var, Export, Default, PartialEq, Eq, Clone, Copy)]
@@ -53,6 +88,79 @@ pub enum SortAxis {
     Z
 }
 
+#[derive(GodotConvert, Var, Export, Default, PartialEq, Eq, Clone, Copy)]
+#[godot(via = i64)]
+pub enum RoomShape {
+    #[default]
+    Box,
+    Ellipsoid,
+    Cylinder,
+    LShape,
+    TShape,
+    Plus,
+    Stamp,
+}
+
+#[derive(GodotConvert, Var, Export, Default, PartialEq, Eq, Clone, Copy)]
+#[godot(via = i64)]
+pub enum DistanceMetric {
+    #[default]
+    Euclidean,
+    Manhattan,
+    Chebyshev,
+    AnisotropicY,
+}
+
+#[derive(GodotConvert, Var, Export, Default, PartialEq, Eq, Clone, Copy)]
+#[godot(via = i64)]
+pub enum ErosionMode {
+    #[default]
+    Hydraulic,
+    Thermal,
+}
+
+#[derive(GodotConvert, Var, Export, Default, PartialEq, Eq, Clone, Copy)]
+#[godot(via = i64)]
+pub enum AssertKind {
+    #[default]
+    MinRooms,
+    Coverage,
+    PathExists,
+    Expression,
+}
+
+#[derive(GodotConvert, Var, Export, Default, PartialEq, Eq, Clone, Copy)]
+#[godot(via = i64)]
+pub enum TransformOp {
+    #[default]
+    Translate,
+    RotateY,
+    Mirror,
+    Crop,
+    CropToSel,
+    Pad,
+    Resize,
+}
+
+#[derive(GodotConvert, Var, Export, Default, PartialEq, Eq, Clone, Copy)]
+#[godot(via = i64)]
+pub enum SymmetryMode {
+    #[default]
+    MirrorX,
+    MirrorZ,
+    MirrorXZ,
+    Rotate2,
+    Rotate4,
+}
+
+#[derive(GodotConvert, Var, Export, Default, PartialEq, Eq, Clone, Copy)]
+#[godot(via = i64)]
+pub enum VoxelizeFill {
+    #[default]
+    Surface,
+    Solid,
+}
+
 #[derive(GodotConvert, Var, Export, Default, PartialEq, Eq, Clone, Copy)]
 #[godot(via = i64)]
 pub enum SetBoolean {
@@ -144,6 +252,12 @@ pub struct MapGenCommand {
     pub max_within : Vector3i,
     #[export]
     pub save_union : GString,
+    #[export]
+    pub room_shape : RoomShape,
+    #[export]
+    pub room_margin : i64,
+    #[export]
+    pub stamp_source : GString,
 
     #[export_group(name = "SortList mode")]
     #[export]
@@ -158,6 +272,10 @@ pub struct MapGenCommand {
     pub vertical_skew: f64,
     #[export]
     pub points_list: GString,
+    #[export]
+    pub rooms_field: GString,
+    #[export]
+    pub save_graph: GString,
 
     #[export_group(name = "ListInput mode")]
     #[export]
@@ -172,6 +290,8 @@ pub struct MapGenCommand {
     pub apply_min: Vector3i,
     #[export]
     pub apply_max: Vector3i,
+    #[export]
+    pub mask: GString,
 
     #[export_group(name = "IntervalSelect mode")]
     #[export]
@@ -186,6 +306,259 @@ pub struct MapGenCommand {
     pub sf_reverse: bool,
     #[export]
     pub column: bool,
+
+    #[export_group(name = "PoissonSample mode")]
+    #[export]
+    pub min_radius: f64,
+    #[export]
+    pub max_radius: f64,
+    #[export]
+    pub radius_field: GString,
+    #[export]
+    pub save_sel: GString,
+
+    #[export_group(name = "RandomWalkers mode")]
+    #[export]
+    pub walker_count: i64,
+    #[export]
+    pub walker_lifetime: i64,
+    #[export]
+    pub turn_chance: f64,
+    #[export]
+    pub vertical_chance: f64,
+    #[export]
+    pub walker_radius: i64,
+
+    #[export_group(name = "Voronoi mode")]
+    #[export]
+    pub metric: DistanceMetric,
+    #[export]
+    #[init(val = 1.0)]
+    pub y_weight: f64,
+    #[export]
+    pub save_borders: GString,
+
+    #[export_group(name = "Heightmap mode")]
+    #[export]
+    pub height_noise: Option<Gd<FastNoiseLite>>,
+    #[export]
+    #[init(val = 1.0)]
+    pub height_scale: f64,
+    #[export]
+    pub height_offset: f64,
+    #[export]
+    pub save_depth: GString,
+
+    #[export_group(name = "Erosion mode")]
+    #[export]
+    pub erosion_mode: ErosionMode,
+    #[export]
+    pub height_layer: i64,
+    #[export]
+    #[init(val = 10000)]
+    pub iterations: i64,
+    // full-grid sweeps for thermal erosion, each far costlier than a droplet
+    #[export]
+    #[init(val = 50)]
+    pub thermal_passes: i64,
+    #[export]
+    #[init(val = 30)]
+    pub droplet_lifetime: i64,
+    #[export]
+    #[init(val = 0.3)]
+    pub erosion_rate: f64,
+    #[export]
+    #[init(val = 0.3)]
+    pub deposition_rate: f64,
+    #[export]
+    #[init(val = 0.01)]
+    pub evaporation_rate: f64,
+    #[export]
+    #[init(val = 0.05)]
+    pub inertia: f64,
+    #[export]
+    #[init(val = 4.0)]
+    pub sediment_capacity: f64,
+    #[export]
+    #[init(val = 0.01)]
+    pub talus: f64,
+    #[export]
+    pub save_flow: GString,
+
+    #[export_group(name = "FloodFill mode")]
+    #[export]
+    pub flood_solid: GString,
+    #[export]
+    pub flood_sources: GString,
+    #[export]
+    #[init(val = -1)]
+    pub water_level: i64,
+
+    #[export_group(name = "Rivers mode")]
+    #[export]
+    pub river_height_layer: i64,
+    #[export]
+    #[init(val = 1.0)]
+    pub river_height_scale: f64,
+    #[export]
+    pub river_height_offset: f64,
+    #[export]
+    pub river_count: i64,
+    #[export]
+    pub river_spacing: f64,
+    #[export]
+    #[init(val = 0.5)]
+    pub river_width: f64,
+    #[export]
+    pub width_per_flow: f64,
+    #[export]
+    #[init(val = 1)]
+    pub river_depth: i64,
+    #[export]
+    pub save_paths: GString,
+    #[export]
+    pub save_river_flow: GString,
+
+    #[export_group(name = "Classify mode")]
+    #[export]
+    pub classifier: Option<Gd<Classifier>>,
+
+    #[export_group(name = "FindDoors mode")]
+    #[export]
+    pub door_rooms: GString,
+    #[export]
+    pub one_door_per_pair: bool,
+    #[export]
+    pub save_orientation: GString,
+    #[export]
+    pub save_room_index: GString,
+
+    #[export_group(name = "LockAndKey mode")]
+    #[export]
+    pub lock_count: i64,
+    #[export]
+    pub save_locks: GString,
+    #[export]
+    pub save_endpoints: GString,
+
+    #[export_group(name = "DijkstraMap mode")]
+    #[export]
+    pub distance_sources: GString,
+    #[export]
+    #[init(val = 1)]
+    pub max_step: i64,
+    #[export]
+    #[init(val = 1)]
+    pub max_drop: i64,
+    #[export]
+    #[init(val = -1.0)]
+    pub unreachable_value: f64,
+
+    #[export_group(name = "Reachability mode")]
+    #[export]
+    pub reach_solid: GString,
+    #[export]
+    pub reach_starts: GString,
+    #[export]
+    #[init(val = 2)]
+    pub player_height: i64,
+    #[export]
+    #[init(val = 1)]
+    pub player_step: i64,
+    #[export]
+    #[init(val = 1)]
+    pub player_drop: i64,
+    #[export]
+    pub save_unreachable: GString,
+    #[export]
+    pub save_count: GString,
+
+    #[export_group(name = "EnsureConnected mode")]
+    #[export]
+    pub weight_field: GString,
+    #[export]
+    pub min_region_size: i64,
+    #[export]
+    #[init(val = 1.0)]
+    pub vertical_cost: f64,
+    #[export]
+    pub save_carved: GString,
+
+    #[export_group(name = "Assert mode")]
+    #[export]
+    pub assert_kind: AssertKind,
+    #[export]
+    pub assert_min: f64,
+    #[export]
+    #[init(val = 1.0)]
+    pub assert_max: f64,
+    #[export]
+    pub assert_expr: GString,
+    #[export]
+    pub assert_points: GString,
+    #[export]
+    #[init(val = 1)]
+    pub assert_step: i64,
+    #[export]
+    #[init(val = 1)]
+    pub assert_drop: i64,
+
+    #[export_group(name = "Transform mode")]
+    #[export]
+    pub transform_op: TransformOp,
+    #[export]
+    pub quarter_turns: i64,
+    #[export]
+    pub mirror_axis: SortAxis,
+    #[export]
+    pub translation: Vector3i,
+    #[export]
+    pub box_min: Vector3i,
+    #[export]
+    pub box_max: Vector3i,
+    #[export]
+    pub pad_before: Vector3i,
+    #[export]
+    pub pad_after: Vector3i,
+    #[export]
+    pub new_size: Vector3i,
+    #[export]
+    pub fill_value: f64,
+
+    #[export_group(name = "Symmetrize mode")]
+    #[export]
+    pub symmetry_mode: SymmetryMode,
+    #[export]
+    pub symmetry_fields: godot::prelude::Array<GString>,
+
+    #[export_group(name = "StampPrefabs mode")]
+    #[export]
+    pub prefabs: godot::prelude::Array<Gd<PackedScene>>,
+    #[export]
+    pub prefab_points: GString,
+    #[export]
+    pub prefab_rooms: GString,
+    #[export]
+    #[init(val = true)]
+    pub random_rotation: bool,
+    #[export]
+    pub prefab_turns: i64,
+    #[export]
+    pub save_prefab_index: GString,
+
+    #[export_group(name = "ImportGridMap mode")]
+    // empty reads the GeneratedGridMap running the generation
+    #[export]
+    pub gridmap_path: NodePath,
+
+    #[export_group(name = "Scene input mode")]
+    // resolved relative to the GeneratedGridMap
+    #[export]
+    pub scene_path: NodePath,
+    #[export]
+    pub path_radius: f64,
+    #[export]
+    pub voxelize_fill: VoxelizeFill,
 }
 
 
@@ -222,7 +595,7 @@ impl MapGenCommand {
         }
     }
 
-    pub fn run_one( &self, seed: i64, mut input: DataGrid, name: String ) -> Result<DataGrid, String> {
+    pub fn run_one( &self, seed: i64, mut input: DataGrid, name: String, scene: &SceneContext ) -> Result<DataGrid, String> {
         match self.mode {
             CommandMode::Expressions => {
                 for e in self.expression_list.iter_shared() {
@@ -295,7 +668,16 @@ impl MapGenCommand {
                 let sizes = RectPrism{ min:( self.min_size.x as usize, self.min_size.y as usize, self.min_size.z as usize ), max:( self.max_size.x as usize, self.max_size.y as usize, self.max_size.z as usize ) };
                 let area = RectPrism{ min:( self.min_within.x as usize, self.min_within.y as usize, self.min_within.z as usize ), max:( self.max_within.x as usize, self.max_within.y as usize, self.max_within.z as usize ) };
 
-                let res = AlgorithmHelper::random_rooms( self.count, seed + self.seed_salt, area, sizes, self.allow_overlap );
+                let mut stamp : Option<&Selection> = None;
+                if self.room_shape == RoomShape::Stamp {
+                    if let Some(GridElement::Sel(sel)) = input.elements.get( &self.stamp_source.to_string() ) {
+                        stamp = Some(sel);
+                    } else {
+                        return Err( format!("Attempted to run RandomRooms command '{}' with stamp shape but a non-boolean stamp source!", name ) );
+                    }
+                }
+
+                let res = AlgorithmHelper::random_rooms( self.count, seed + self.seed_salt, area, sizes, self.allow_overlap, self.room_shape, self.room_margin, stamp );
 
                 if let Ok( ( vec, uni ) ) = res {
                     input.elements.insert( self.save.to_string(), GridElement::Rooms( vec ) );
@@ -343,16 +725,36 @@ impl MapGenCommand {
                         let sm = SearchMap{ weight_array: arr, max_slope: self.max_slope.abs(), vertical_skew: (self.vertical_skew as f32).abs() };
                         let mut uni = Box::new( HashSet::<(i64, i64, i64)>::new() );
 
-                        for ridx in 0..(vec.len() - 1) {
+                        // with a graph requested, points are matched to the rooms they're the center of (or inside) and each carved path becomes an edge
+                        let mut graph : Option<RoomGraph> = None;
+                        if !self.save_graph.is_empty() {
+                            if let Some(GridElement::Rooms(rooms)) = input.elements.get( &self.rooms_field.to_string() ) {
+                                graph = Some( RoomGraph{ rooms: rooms.clone(), edges: Vec::new() } );
+                            } else {
+                                return Err( format!("Attempted to run CarvePaths command '{}' with a graph output but a non-rooms rooms field!", name ) );
+                            }
+                        }
+
+                        for ridx in 0..vec.len().saturating_sub(1) {
                             let ca = vec[ridx];
                             let cb = vec[ridx + 1];
                             let result = sm.thstar( ca, cb );
                             if let Ok( path ) = result {
+                                if let Some(ref mut g) = graph {
+                                    if let ( Some(a), Some(b) ) = ( g.room_at( ca ), g.room_at( cb ) ) {
+                                        if a != b {
+                                            g.edges.push( RoomEdge{ a, b, kind: EdgeKind::Corridor, cells: path.clone() } );
+                                        }
+                                    }
+                                }
                                 uni = Box::new( &*uni | &*path );
                             }
                         }
 
                         input.elements.insert( self.save.to_string(), GridElement::Sel(uni) );
+                        if let Some(g) = graph {
+                            input.elements.insert( self.save_graph.to_string(), GridElement::Graph(g) );
+                        }
                         return Ok(input);
                     } else {
                         return Err( format!("Attempted to run CarvePaths command '{}' without providing a set of rooms to connect!", name ) );
@@ -390,6 +792,10 @@ impl MapGenCommand {
                     let list = Vec::from_iter( vec.into_iter().map( |r| r.center ) );
                     input.elements.insert( self.save.to_string(), GridElement::List(list) );
                     return Ok(input);
+                } else if let Some(GridElement::Graph(graph)) = input.elements.get( &self.source.to_string() ) {
+                    let list = Vec::from_iter( graph.rooms.iter().map( |r| r.center ) );
+                    input.elements.insert( self.save.to_string(), GridElement::List(list) );
+                    return Ok(input);
                 } else {
                     return Err( format!("Attempted to run GetRoomCenters command '{}' with a non-rooms source!", name ) );
                 }
@@ -425,9 +831,24 @@ impl MapGenCommand {
             },
             CommandMode::CellularAutomata => {
                 if let Some(rule) = &self.ca_rule {
+                    let sz = input.size;
+                    if self.apply_min.x < 0 || self.apply_min.y < 0 || self.apply_min.z < 0 ||
+                        self.apply_max.x > sz.0 as i32 || self.apply_max.y > sz.1 as i32 || self.apply_max.z > sz.2 as i32 ||
+                        self.apply_min.x > self.apply_max.x || self.apply_min.y > self.apply_max.y || self.apply_min.z > self.apply_max.z {
+                        return Err( format!("CellularAutomata command '{}' has an apply area that doesn't fit inside the {}x{}x{} grid!", name, sz.0, sz.1, sz.2 ) );
+                    }
+                    // the mask is read before the source is taken out, so a field can mask itself
+                    let mut mask : Option<Selection> = None;
+                    if !self.mask.is_empty() {
+                        if let Some(GridElement::Sel(sel)) = input.elements.get( &self.mask.to_string() ) {
+                            mask = Some( sel.clone() );
+                        } else {
+                            return Err( format!("Attempted to run CellularAutomata command '{}' with a non-boolean mask!", name ) );
+                        }
+                    }
                     let data = input.elements.remove( &self.source.to_string() );
                     if let Some(ge) = data {
-                        let res = rule.bind().run( ge, RectPrism{ min:(self.apply_min.x as usize, self.apply_min.y as usize, self.apply_min.z as usize), max: (self.apply_max.x as usize, self.apply_max.y as usize, self.apply_max.z as usize) }, self.steps as usize );
+                        let res = rule.bind().run( ge, RectPrism{ min:(self.apply_min.x as usize, self.apply_min.y as usize, self.apply_min.z as usize), max: (self.apply_max.x as usize, self.apply_max.y as usize, self.apply_max.z as usize) }, sz, self.steps as usize, seed + self.seed_salt, mask.as_ref() );
                         if res.is_err() {
                             return Err( format!("CellularAutomata command '{}' errored out with '{}'", name, res.err().unwrap() ) );
                         } else {
@@ -458,6 +879,7 @@ impl MapGenCommand {
                 if let Some(GridElement::Sel(sel)) = input.elements.get( &self.source.to_string() ) {
                     if let Some(GridElement::Sel(wall)) = input.elements.get( &self.solid.to_string() ) {
                         let mut output = Box::new( HashSet::<(i64, i64, i64)>::new() );
+                        let height = input.size.1 as i64;
                         for pos in sel.clone().into_iter() {
                             let mut prev = pos;
                             let mut fore = pos;
@@ -466,7 +888,8 @@ impl MapGenCommand {
                             } else {
                                 fore = ( fore.0, fore.1 - 1, fore.2 );
                             }
-                            while !wall.contains(&fore) {
+                            // stop at the grid edge when there's nothing solid to land on
+                            while !wall.contains(&fore) && fore.1 >= 0 && fore.1 < height {
                                 if self.column {
                                     output.insert(prev);
                                 }
@@ -488,6 +911,679 @@ impl MapGenCommand {
                     return Err( format!("Attempted to run SelectFall command '{}' with a non-boolean source!", name ) );
                 }
             },
+            CommandMode::PoissonSample => {
+                if self.min_radius <= 0.0 {
+                    return Err( format!("PoissonSample command '{}' needs a positive minimum radius!", name ) );
+                }
+
+                let candidates : PosList;
+                if self.source.is_empty() {
+                    let sz = input.size;
+                    candidates = Vec::from_iter( ndarray::indices( sz ).into_iter().map( |(x, y, z)| (x as i64, y as i64, z as i64) ) );
+                } else if let Some(GridElement::Sel(sel)) = input.elements.get( &self.source.to_string() ) {
+                    // selections can hold cells outside the grid, which have no radius to read
+                    candidates = Vec::from_iter( sel.iter().cloned().filter( |p| in_grid( input.size, *p ).is_some() ) );
+                } else {
+                    return Err( format!("Attempted to run PoissonSample command '{}' with a non-boolean mask source!", name ) );
+                }
+
+                // the radius field is read as 0..1 and blends between the minimum and maximum radius
+                let max_radius = self.max_radius.max( self.min_radius );
+                let mut radii : Option<&Array3<f64>> = None;
+                if !self.radius_field.is_empty() {
+                    if let Some(GridElement::Float(arr)) = input.elements.get( &self.radius_field.to_string() ) {
+                        radii = Some(arr);
+                    } else {
+                        return Err( format!("Attempted to run PoissonSample command '{}' with a non-float radius field!", name ) );
+                    }
+                }
+
+                let points = AlgorithmHelper::poisson_sample( candidates, seed + self.seed_salt, max_radius, | p | {
+                    match radii {
+                        Some(arr) => {
+                            let t = arr[[ p.0 as usize, p.1 as usize, p.2 as usize ]].clamp( 0.0, 1.0 );
+                            self.min_radius + ( max_radius - self.min_radius ) * t
+                        },
+                        None => self.min_radius,
+                    }
+                } );
+
+                if !self.save_sel.is_empty() {
+                    let sel = Box::new( HashSet::<(i64, i64, i64)>::from_iter( points.iter().cloned() ) );
+                    input.elements.insert( self.save_sel.to_string(), GridElement::Sel(sel) );
+                }
+                if !self.save.is_empty() {
+                    input.elements.insert( self.save.to_string(), GridElement::List(points) );
+                }
+                return Ok(input);
+            },
+            CommandMode::RandomWalkers => {
+                if let Some(GridElement::List(starts)) = input.elements.get( &self.source.to_string() ) {
+                    let walkers = RandomWalkers{
+                        count: self.walker_count.max(0) as usize,
+                        lifetime: self.walker_lifetime.max(0) as usize,
+                        turn_chance: self.turn_chance,
+                        vertical_chance: self.vertical_chance,
+                        radius: self.walker_radius.max(0),
+                    };
+                    let res = walkers.walk( starts, input.size, seed + self.seed_salt );
+                    if res.is_err() {
+                        return Err( format!("RandomWalkers command '{}' errored out with '{}'", name, res.err().unwrap() ) );
+                    }
+                    let mut carved = res.unwrap();
+
+                    // carve into an existing selection when the save field already holds one
+                    if let Some(GridElement::Sel(existing)) = input.elements.get( &self.save.to_string() ) {
+                        carved = Box::new( &**existing | &*carved );
+                    }
+                    input.elements.insert( self.save.to_string(), GridElement::Sel(carved) );
+                    return Ok(input);
+                } else {
+                    return Err( format!("Attempted to run RandomWalkers command '{}' with a non-list source!", name ) );
+                }
+            },
+            CommandMode::Voronoi => {
+                if let Some(GridElement::List(points)) = input.elements.get( &self.source.to_string() ) {
+                    if points.is_empty() {
+                        return Err( format!("Attempted to run Voronoi command '{}' with an empty point list!", name ) );
+                    }
+                    let (regions, borders) = AlgorithmHelper::voronoi( points, input.size, self.metric, self.y_weight );
+                    input.elements.insert( self.save.to_string(), GridElement::Int(regions) );
+                    if !self.save_borders.is_empty() {
+                        input.elements.insert( self.save_borders.to_string(), GridElement::Sel(borders) );
+                    }
+                    return Ok(input);
+                } else {
+                    return Err( format!("Attempted to run Voronoi command '{}' with a non-list source!", name ) );
+                }
+            },
+            CommandMode::Heightmap => {
+                let sz = input.size;
+                let heights : Array2<f64>;
+
+                // a float source field gives heights from its top layer, otherwise the noise is sampled over X/Z
+                if !self.source.is_empty() {
+                    if let Some(GridElement::Float(arr)) = input.elements.get( &self.source.to_string() ) {
+                        heights = Array::from_shape_fn( (sz.0, sz.2), |(x, z)| arr[[ x, sz.1 - 1, z ]] );
+                    } else {
+                        return Err( format!("Attempted to run Heightmap command '{}' with a non-float source!", name ) );
+                    }
+                } else if let Some(noise) = &self.height_noise {
+                    let mut noise = noise.clone();
+                    noise.set_seed( (seed + self.seed_salt) as i32 );
+                    heights = Array::from_shape_fn( (sz.0, sz.2), |(x, z)| noise.get_noise_2d( x as f32, z as f32 ) as f64 );
+                } else {
+                    return Err( format!("Heightmap command '{}' had neither a source field nor noise supplied!", name ) );
+                }
+
+                let mut filled = Box::new( HashSet::<(i64, i64, i64)>::new() );
+                let mut depth = Array3::<i64>::from_elem( sz, -1 );
+
+                for ((x, z), h) in heights.indexed_iter() {
+                    let surface = ( self.height_offset + self.height_scale * h ).floor().min( sz.1 as f64 - 1.0 ) as i64;
+                    for y in 0..=surface {
+                        filled.insert( (x as i64, y, z as i64) );
+                        depth[[ x, y as usize, z ]] = surface - y;
+                    }
+                }
+
+                input.elements.insert( self.save.to_string(), GridElement::Sel(filled) );
+                if !self.save_depth.is_empty() {
+                    input.elements.insert( self.save_depth.to_string(), GridElement::Int(depth) );
+                }
+                return Ok(input);
+            },
+            CommandMode::Erosion => {
+                if let Some(GridElement::Float(arr)) = input.elements.get( &self.source.to_string() ) {
+                    if self.height_layer < 0 || self.height_layer as usize >= input.size.1 {
+                        return Err( format!("Erosion command '{}' has a height layer outside the grid!", name ) );
+                    }
+                    let layer = self.height_layer as usize;
+                    let mut heights = arr.index_axis( Axis(1), layer ).to_owned();
+
+                    let erosion = Erosion{
+                        iterations: self.iterations.max(0) as usize,
+                        thermal_passes: self.thermal_passes.max(0) as usize,
+                        lifetime: self.droplet_lifetime.max(0) as usize,
+                        erosion_rate: self.erosion_rate,
+                        deposition_rate: self.deposition_rate,
+                        evaporation_rate: self.evaporation_rate,
+                        inertia: self.inertia.clamp( 0.0, 1.0 ),
+                        capacity: self.sediment_capacity,
+                        talus: self.talus,
+                    };
+
+                    let flow = match self.erosion_mode {
+                        ErosionMode::Hydraulic => erosion.hydraulic( &mut heights, seed + self.seed_salt ),
+                        ErosionMode::Thermal => erosion.thermal( &mut heights ),
+                    };
+
+                    let mut eroded = arr.clone();
+                    eroded.index_axis_mut( Axis(1), layer ).assign( &heights );
+                    input.elements.insert( self.save.to_string(), GridElement::Float(eroded) );
+
+                    if !self.save_flow.is_empty() {
+                        let mut flow_field = Array3::<f64>::zeros( input.size );
+                        flow_field.index_axis_mut( Axis(1), layer ).assign( &flow );
+                        input.elements.insert( self.save_flow.to_string(), GridElement::Float(flow_field) );
+                    }
+                    return Ok(input);
+                } else {
+                    return Err( format!("Attempted to run Erosion command '{}' with a non-float source!", name ) );
+                }
+            },
+            CommandMode::FloodFill => {
+                if let Some(GridElement::Sel(wall)) = input.elements.get( &self.flood_solid.to_string() ) {
+                    let fluid = FluidFill{ solid: wall, size: input.size };
+                    let water : Selection;
+
+                    // with source points the water level only caps how high basins fill, without them every sealed basin is filled to it
+                    if !self.flood_sources.is_empty() {
+                        if let Some(GridElement::List(sources)) = input.elements.get( &self.flood_sources.to_string() ) {
+                            water = fluid.fill_from_sources( sources, if self.water_level < 0 { None } else { Some(self.water_level) } );
+                        } else {
+                            return Err( format!("Attempted to run FloodFill command '{}' with a non-list points list!", name ) );
+                        }
+                    } else if self.water_level >= 0 {
+                        water = fluid.fill_to_level( self.water_level );
+                    } else {
+                        return Err( format!("FloodFill command '{}' needs either a water level or a list of source points!", name ) );
+                    }
+
+                    input.elements.insert( self.save.to_string(), GridElement::Sel(water) );
+                    return Ok(input);
+                } else {
+                    return Err( format!("Attempted to run FloodFill command '{}' with a non-boolean solid field!", name ) );
+                }
+            },
+            CommandMode::Rivers => {
+                if let Some(GridElement::Float(arr)) = input.elements.get( &self.source.to_string() ) {
+                    if self.river_height_layer < 0 || self.river_height_layer as usize >= input.size.1 {
+                        return Err( format!("Rivers command '{}' has a height layer outside the grid!", name ) );
+                    }
+                    let layer = self.river_height_layer as usize;
+                    let heights = arr.index_axis( Axis(1), layer ).to_owned();
+
+                    let rivers = Rivers{
+                        count: self.river_count.max(0) as usize,
+                        spacing: self.river_spacing,
+                        width: self.river_width,
+                        width_per_flow: self.width_per_flow,
+                        depth: self.river_depth.max(1),
+                        height_scale: self.river_height_scale,
+                        height_offset: self.river_height_offset,
+                    };
+                    let result = rivers.generate( &heights, input.size.1 );
+
+                    // each river is its own list, named by the path prefix followed by its index
+                    if !self.save_paths.is_empty() {
+                        for (idx, path) in result.paths.into_iter().enumerate() {
+                            input.elements.insert( format!( "{}{}", self.save_paths, idx ), GridElement::List(path) );
+                        }
+                    }
+                    if !self.save_river_flow.is_empty() {
+                        let mut flow_field = Array3::<f64>::zeros( input.size );
+                        flow_field.index_axis_mut( Axis(1), layer ).assign( &result.flow );
+                        input.elements.insert( self.save_river_flow.to_string(), GridElement::Float(flow_field) );
+                    }
+                    input.elements.insert( self.save.to_string(), GridElement::Sel(result.carved) );
+                    return Ok(input);
+                } else {
+                    return Err( format!("Attempted to run Rivers command '{}' with a non-float height source!", name ) );
+                }
+            },
+            CommandMode::Classify => {
+                if let Some(classifier) = &self.classifier {
+                    let res = classifier.bind().classify( &input, seed + self.seed_salt );
+                    if res.is_err() {
+                        return Err( format!("Classify command '{}' errored out with '{}'", name, res.err().unwrap() ) );
+                    }
+                    input.elements.insert( self.save.to_string(), GridElement::Int( res.unwrap() ) );
+                    return Ok(input);
+                } else {
+                    return Err( format!("Attempted to run Classify command '{}' without a classifier set!", name ) );
+                }
+            },
+            CommandMode::FindDoors => {
+                let Some(GridElement::Sel(corridor)) = input.elements.get( &self.source.to_string() ) else {
+                    return Err( format!("Attempted to run FindDoors command '{}' with a non-boolean corridor source!", name ) );
+                };
+                let mut doors = match input.elements.get( &self.door_rooms.to_string() ) {
+                    Some(GridElement::Rooms(rooms)) => AlgorithmHelper::find_doors( corridor, rooms, None, self.one_door_per_pair ),
+                    Some(GridElement::Graph(graph)) => AlgorithmHelper::find_doors( corridor, &graph.rooms, Some( graph.edges.as_slice() ), self.one_door_per_pair ),
+                    _ => { return Err( format!("Attempted to run FindDoors command '{}' with a rooms field that holds neither rooms nor a room graph!", name ) ); },
+                };
+                // corridor selections can reach outside the grid, doors there have no cell to write to
+                doors.retain( |d| in_grid( input.size, d.pos ).is_some() );
+
+                // orientations are written into an existing Int field when there is one, so doors can go straight into 'rotation'
+                if !self.save_orientation.is_empty() {
+                    let mut orient = match input.elements.remove( &self.save_orientation.to_string() ) {
+                        Some(GridElement::Int(arr)) => arr,
+                        _ => Array3::<i64>::zeros( input.size ),
+                    };
+                    for d in doors.iter() {
+                        orient[[ d.pos.0 as usize, d.pos.1 as usize, d.pos.2 as usize ]] = d.orientation;
+                    }
+                    input.elements.insert( self.save_orientation.to_string(), GridElement::Int(orient) );
+                }
+                if !self.save_room_index.is_empty() {
+                    let mut index = Array3::<i64>::from_elem( input.size, -1 );
+                    for d in doors.iter() {
+                        index[[ d.pos.0 as usize, d.pos.1 as usize, d.pos.2 as usize ]] = d.room as i64;
+                    }
+                    input.elements.insert( self.save_room_index.to_string(), GridElement::Int(index) );
+                }
+                input.elements.insert( self.save.to_string(), GridElement::List( Vec::from_iter( doors.iter().map( |d| d.pos ) ) ) );
+                return Ok(input);
+            },
+            CommandMode::LockAndKey => {
+                if let Some(GridElement::Graph(mut graph)) = input.elements.remove( &self.source.to_string() ) {
+                    let res = progression::lock_and_key( &mut graph, self.lock_count.max(0) as usize, seed + self.seed_salt );
+                    if res.is_err() {
+                        return Err( format!("LockAndKey command '{}' errored out with '{}'", name, res.err().unwrap() ) );
+                    }
+                    let prog = res.unwrap();
+
+                    // the start and goal rooms' centers, in that order
+                    if !self.save_endpoints.is_empty() {
+                        let ends = vec![ graph.rooms[prog.start].center, graph.rooms[prog.goal].center ];
+                        input.elements.insert( self.save_endpoints.to_string(), GridElement::List(ends) );
+                    }
+                    input.elements.insert( self.save.to_string(), GridElement::List(prog.keys) );
+                    if !self.save_locks.is_empty() {
+                        input.elements.insert( self.save_locks.to_string(), GridElement::List(prog.locks) );
+                    }
+                    input.elements.insert( self.source.to_string(), GridElement::Graph(graph) );
+                    return Ok(input);
+                } else {
+                    return Err( format!("Attempted to run LockAndKey command '{}' with a non-graph source!", name ) );
+                }
+            },
+            CommandMode::DijkstraMap => {
+                if let Some(GridElement::Sel(walkable)) = input.elements.get( &self.source.to_string() ) {
+                    if let Some(GridElement::List(sources)) = input.elements.get( &self.distance_sources.to_string() ) {
+                        let dm = DistanceMap{ walkable, max_step: self.max_step.max(0), max_drop: self.max_drop.max(0) };
+                        let mut dist = dm.compute( sources, input.size );
+                        dist.mapv_inplace( |d| if d.is_finite() { d } else { self.unreachable_value } );
+                        input.elements.insert( self.save.to_string(), GridElement::Float(dist) );
+                        return Ok(input);
+                    } else {
+                        return Err( format!("Attempted to run DijkstraMap command '{}' without providing a list of start points!", name ) );
+                    }
+                } else {
+                    return Err( format!("Attempted to run DijkstraMap command '{}' with a non-boolean walkable source!", name ) );
+                }
+            },
+            CommandMode::Reachability => {
+                if let Some(GridElement::Sel(wall)) = input.elements.get( &self.reach_solid.to_string() ) {
+                    if let Some(GridElement::List(starts)) = input.elements.get( &self.reach_starts.to_string() ) {
+                        let capsule = Capsule{ solid: wall, size: input.size, height: self.player_height.max(1), max_step: self.player_step.max(0), max_drop: self.player_drop.max(0) };
+                        let (reached, unreached) = capsule.reachability( starts );
+
+                        // the count stat holds the number of standing positions the player can't get to
+                        if !self.save_count.is_empty() {
+                            input.stats.insert( self.save_count.to_string(), unreached.len() as f64 );
+                        }
+                        if !self.save_unreachable.is_empty() {
+                            input.elements.insert( self.save_unreachable.to_string(), GridElement::Sel(unreached) );
+                        }
+                        input.elements.insert( self.save.to_string(), GridElement::Sel(reached) );
+                        return Ok(input);
+                    } else {
+                        return Err( format!("Attempted to run Reachability command '{}' without providing a list of start points!", name ) );
+                    }
+                } else {
+                    return Err( format!("Attempted to run Reachability command '{}' with a non-boolean solid field!", name ) );
+                }
+            },
+            CommandMode::EnsureConnected => {
+                if let Some(GridElement::Sel(open)) = input.elements.get( &self.source.to_string() ) {
+                    // without a weights field every cell costs the same to carve through
+                    let weights : Array3<f64>;
+                    if self.weight_field.is_empty() {
+                        weights = Array3::<f64>::ones( input.size );
+                    } else if let Some(GridElement::Float(arr)) = input.elements.get( &self.weight_field.to_string() ) {
+                        weights = arr.clone();
+                    } else {
+                        return Err( format!("Attempted to run EnsureConnected command '{}' with a non-float weights field!", name ) );
+                    }
+
+                    let connector = Connector{ weights, vertical_cost: self.vertical_cost.max(0.0) };
+                    let (joined, carved, unreached) = connector.ensure_connected( open, self.min_region_size.max(0) as usize );
+                    if unreached > 0 {
+                        godot_warn!("EnsureConnected command '{}' couldn't reach {} regions through the weights field; they were dropped.", name, unreached );
+                    }
+
+                    if !self.save_carved.is_empty() {
+                        input.elements.insert( self.save_carved.to_string(), GridElement::Sel(carved) );
+                    }
+                    input.elements.insert( self.save.to_string(), GridElement::Sel(joined) );
+                    return Ok(input);
+                } else {
+                    return Err( format!("Attempted to run EnsureConnected command '{}' with a non-boolean source!", name ) );
+                }
+            },
+            CommandMode::Assert => {
+                let failure : Option<String>;
+                match self.assert_kind {
+                    AssertKind::MinRooms => {
+                        let count = match input.elements.get( &self.source.to_string() ) {
+                            Some(GridElement::Rooms(rms)) => rms.len(),
+                            Some(GridElement::Graph(graph)) => graph.rooms.len(),
+                            _ => { return Err( format!("Attempted to run Assert command '{}' counting rooms on a field holding neither rooms nor a room graph!", name ) ); },
+                        };
+                        failure = if ( count as f64 ) < self.assert_min { Some( format!( "only {} rooms, wanted at least {}", count, self.assert_min ) ) } else { None };
+                    },
+                    AssertKind::Coverage => {
+                        // share of selected cells, or of nonzero cells for Int/Float fields
+                        let cells = ( input.size.0 * input.size.1 * input.size.2 ).max(1) as f64;
+                        let covered = match input.elements.get( &self.source.to_string() ) {
+                            Some(GridElement::Sel(sel)) => sel.len(),
+                            Some(GridElement::Int(arr)) => arr.iter().filter( |v| **v != 0 ).count(),
+                            Some(GridElement::Float(arr)) => arr.iter().filter( |v| **v != 0.0 ).count(),
+                            _ => { return Err( format!("Attempted to run Assert command '{}' measuring coverage of a field without cells!", name ) ); },
+                        };
+                        let ratio = covered as f64 / cells;
+                        failure = if ratio < self.assert_min || ratio > self.assert_max { Some( format!( "coverage {} outside {}..{}", ratio, self.assert_min, self.assert_max ) ) } else { None };
+                    },
+                    AssertKind::PathExists => {
+                        let Some(GridElement::Sel(walkable)) = input.elements.get( &self.source.to_string() ) else {
+                            return Err( format!("Attempted to run Assert command '{}' finding a path over a non-boolean walkable source!", name ) );
+                        };
+                        let Some(GridElement::List(points)) = input.elements.get( &self.assert_points.to_string() ) else {
+                            return Err( format!("Attempted to run Assert command '{}' finding a path without a list of points!", name ) );
+                        };
+                        if points.len() < 2 {
+                            return Err( format!("Assert command '{}' needs two points to find a path between!", name ) );
+                        }
+                        let dm = DistanceMap{ walkable, max_step: self.assert_step.max(0), max_drop: self.assert_drop.max(0) };
+                        let dist = dm.compute( &vec![ points[0] ], input.size );
+                        let end = points[1];
+                        let reached = in_grid( input.size, end ).is_some_and( |idx| dist[idx].is_finite() );
+                        failure = if reached { None } else { Some( format!( "no path from {:?} to {:?}", points[0], end ) ) };
+                    },
+                    AssertKind::Expression => {
+                        // the expression gets a 'stats' dictionary, see DataGrid::summary
+                        let mut expression = Expression::new_gd();
+                        let pres = expression.parse_ex( &self.assert_expr ).input_names( &PackedStringArray::from( [ GString::from("stats") ] ) ).done();
+                        if !( pres == godot::global::Error::OK ) {
+                            return Err( format!("Assert command '{}' expression parse error: {:?}", name, pres ) );
+                        }
+                        let help = Helper::new_alloc();
+                        let vari = expression.execute_ex().inputs( &array![ &input.summary().to_variant() ] ).base_instance( &help.clone().upcast::<Object>() ).done();
+                        help.free();
+                        if expression.has_execute_failed() {
+                            return Err( format!("Assert command '{}' expression failed to execute: {}", name, expression.get_error_text() ) );
+                        }
+                        failure = if vari.try_to::<bool>().unwrap_or(false) { None } else { Some( format!( "'{}' was false", self.assert_expr ) ) };
+                    },
+                }
+
+                if let Some(msg) = failure {
+                    input.failed_assertions.push( format!( "Assert command '{}': {}", name, msg ) );
+                }
+                return Ok(input);
+            },
+            CommandMode::Transform => {
+                if self.transform_op == TransformOp::Crop && ( self.box_min.x >= self.box_max.x || self.box_min.y >= self.box_max.y || self.box_min.z >= self.box_max.z ) {
+                    return Err( format!("Transform command '{}' was set to an empty crop box!", name ) );
+                }
+
+                let v = | v: Vector3i | ( v.x as i64, v.y as i64, v.z as i64 );
+                let transform = match self.transform_op {
+                    TransformOp::Translate => GridTransform::translate( input.size, v( self.translation ) ),
+                    TransformOp::RotateY => GridTransform::rotate_y( input.size, self.quarter_turns ),
+                    TransformOp::Mirror => GridTransform::mirror( input.size, match self.mirror_axis { SortAxis::X => 0, SortAxis::Y => 1, SortAxis::Z => 2 } ),
+                    TransformOp::Crop => GridTransform::crop( v( self.box_min ), v( self.box_max ) ),
+                    TransformOp::CropToSel => {
+                        if let Some(GridElement::Sel(sel)) = input.elements.get( &self.source.to_string() ) {
+                            if sel.is_empty() {
+                                return Err( format!("Attempted to run Transform command '{}' cropping to an empty selection!", name ) );
+                            }
+                            let min = ( sel.iter().map( |p| p.0 ).min().unwrap(), sel.iter().map( |p| p.1 ).min().unwrap(), sel.iter().map( |p| p.2 ).min().unwrap() );
+                            let max = ( sel.iter().map( |p| p.0 ).max().unwrap() + 1, sel.iter().map( |p| p.1 ).max().unwrap() + 1, sel.iter().map( |p| p.2 ).max().unwrap() + 1 );
+                            GridTransform::crop( min, max )
+                        } else {
+                            return Err( format!("Attempted to run Transform command '{}' cropping to a non-boolean source!", name ) );
+                        }
+                    },
+                    TransformOp::Pad => {
+                        if self.pad_before.x < 0 || self.pad_before.y < 0 || self.pad_before.z < 0 || self.pad_after.x < 0 || self.pad_after.y < 0 || self.pad_after.z < 0 {
+                            return Err( format!("Transform command '{}' was set to negative padding!", name ) );
+                        }
+                        GridTransform::pad( input.size, v( self.pad_before ), v( self.pad_after ) )
+                    },
+                    TransformOp::Resize => {
+                        if self.new_size.x < 1 || self.new_size.y < 1 || self.new_size.z < 1 {
+                            return Err( format!("Transform command '{}' was set to invalid size!", name ) );
+                        }
+                        GridTransform::resize( ( self.new_size.x as usize, self.new_size.y as usize, self.new_size.z as usize ) )
+                    },
+                };
+
+                return Ok( transform.apply( input, self.fill_value ) );
+            },
+            CommandMode::Symmetrize => {
+                let symmetry = Symmetry{ mode: self.symmetry_mode, size: input.size };
+                if let Err(e) = symmetry.validate() {
+                    return Err( format!("Symmetrize command '{}' errored out with '{}'", name, e ) );
+                }
+
+                // an empty field list means every field that can be symmetrized
+                let fields : Vec<String> = if self.symmetry_fields.is_empty() {
+                    input.elements.iter()
+                        .filter( |(_, ge)| !matches!( ge, GridElement::Rooms(_) | GridElement::Graph(_) ) )
+                        .map( |(k, _)| k.clone() )
+                        .collect()
+                } else {
+                    self.symmetry_fields.iter_shared().map( |f| f.to_string() ).collect()
+                };
+
+                for f in fields {
+                    let Some(ge) = input.elements.remove( &f ) else {
+                        return Err( format!("Attempted to run Symmetrize command '{}' on missing field '{}'!", name, f ) );
+                    };
+                    match symmetry.apply( ge, f == "rotation" ) {
+                        Ok(ge) => { input.elements.insert( f, ge ); },
+                        Err(e) => { return Err( format!("Symmetrize command '{}' errored out with '{}'", name, e ) ); },
+                    }
+                }
+                return Ok(input);
+            },
+            CommandMode::StampPrefabs => {
+                let mut prefabs = vec![];
+                for scene in self.prefabs.iter_shared() {
+                    match Prefab::from_scene( &scene ) {
+                        Ok(p) => prefabs.push( p ),
+                        Err(e) => { return Err( format!("StampPrefabs command '{}' errored out with '{}'", name, e ) ); },
+                    }
+                }
+
+                // existing occupied cells are avoided and kept
+                let mut occupied : Selection = match input.elements.get( &self.save.to_string() ) {
+                    Some(GridElement::Sel(sel)) => sel.clone(),
+                    Some(_) => { return Err( format!("Attempted to run StampPrefabs command '{}' saving over a non-boolean field!", name ) ); },
+                    None => Box::new( HashSet::new() ),
+                };
+
+                let stamper = PrefabStamper{ prefabs: &prefabs, size: input.size, turns: if self.random_rotation { None } else { Some( self.prefab_turns ) } };
+                let placements = if !self.prefab_points.is_empty() {
+                    let Some(GridElement::List(points)) = input.elements.get( &self.prefab_points.to_string() ) else {
+                        return Err( format!("Attempted to run StampPrefabs command '{}' on a non-list points field!", name ) );
+                    };
+                    let anchors : Vec<Anchor> = points.iter().map( |p| Anchor::Point( *p ) ).collect();
+                    stamper.stamp( &anchors, &mut occupied, seed + self.seed_salt )
+                } else {
+                    let rooms = match input.elements.get( &self.prefab_rooms.to_string() ) {
+                        Some(GridElement::Rooms(rooms)) => rooms,
+                        Some(GridElement::Graph(graph)) => &graph.rooms,
+                        _ => { return Err( format!("Attempted to run StampPrefabs command '{}' without a points list or rooms field!", name ) ); },
+                    };
+                    let anchors : Vec<Anchor> = rooms.iter().map( |r| Anchor::Room( r ) ).collect();
+                    stamper.stamp( &anchors, &mut occupied, seed + self.seed_salt )
+                };
+
+                let mut mesh = match input.elements.remove( "mesh" ) {
+                    Some(GridElement::Int(arr)) => arr,
+                    Some(_) => { return Err( format!("Attempted to run StampPrefabs command '{}' with a non-integer 'mesh' field!", name ) ); },
+                    None => Array3::<i64>::from_elem( input.size, -1 ),
+                };
+                let mut rotation = match input.elements.remove( "rotation" ) {
+                    Some(GridElement::Int(arr)) => arr,
+                    Some(_) => { return Err( format!("Attempted to run StampPrefabs command '{}' with a non-integer 'rotation' field!", name ) ); },
+                    None => Array3::<i64>::zeros( input.size ),
+                };
+                for pl in placements.iter() {
+                    for &(p, m, o) in pl.cells.iter() {
+                        let idx = [ p.0 as usize, p.1 as usize, p.2 as usize ];
+                        mesh[idx] = m;
+                        rotation[idx] = o;
+                    }
+                }
+
+                // which of the prefabs covers each cell, -1 where none does
+                if !self.save_prefab_index.is_empty() {
+                    let mut index = Array3::<i64>::from_elem( input.size, -1 );
+                    for pl in placements.iter() {
+                        for (p, _m, _o) in pl.cells.iter() {
+                            index[[ p.0 as usize, p.1 as usize, p.2 as usize ]] = pl.prefab as i64;
+                        }
+                    }
+                    input.elements.insert( self.save_prefab_index.to_string(), GridElement::Int(index) );
+                }
+                input.elements.insert( "mesh".to_string(), GridElement::Int(mesh) );
+                input.elements.insert( "rotation".to_string(), GridElement::Int(rotation) );
+                input.elements.insert( self.save.to_string(), GridElement::Sel(occupied) );
+                return Ok(input);
+            },
+            CommandMode::ImportGridMap => {
+                // a GeneratedGridMap's cells are read back relative to where it places its results
+                let (grid_map, offset) = if self.gridmap_path.is_empty() {
+                    ( scene.grid_map.clone(), scene.placement_offset )
+                } else {
+                    let Some(node) = scene.resolve( &self.gridmap_path ) else {
+                        return Err( format!("ImportGridMap command '{}' found no node at '{}'!", name, self.gridmap_path ) );
+                    };
+                    if node.instance_id() == scene.grid_map.instance_id() {
+                        ( scene.grid_map.clone(), scene.placement_offset )
+                    } else if let Ok(generated) = node.clone().try_cast::<GeneratedGridMap>() {
+                        let offset = generated.bind().editor_placement_offset;
+                        ( generated.upcast::<GridMap>(), offset )
+                    } else if let Ok(grid_map) = node.try_cast::<GridMap>() {
+                        ( grid_map, Vector3i::ZERO )
+                    } else {
+                        return Err( format!("ImportGridMap command '{}' was pointed at '{}', which isn't a GridMap!", name, self.gridmap_path ) );
+                    }
+                };
+
+                let mut mesh = Array3::<i64>::from_elem( input.size, -1 );
+                let mut rotation = Array3::<i64>::zeros( input.size );
+                let mut occupied = Box::new( HashSet::<(i64, i64, i64)>::new() );
+                for cell in grid_map.get_used_cells().iter_shared() {
+                    let p = cell - offset;
+                    let pos = ( p.x as i64, p.y as i64, p.z as i64 );
+                    if let Some(idx) = in_grid( input.size, pos ) {
+                        mesh[idx] = grid_map.get_cell_item( cell ) as i64;
+                        rotation[idx] = grid_map.get_cell_item_orientation( cell ) as i64;
+                        occupied.insert( pos );
+                    }
+                }
+
+                input.elements.insert( "mesh".to_string(), GridElement::Int(mesh) );
+                input.elements.insert( "rotation".to_string(), GridElement::Int(rotation) );
+                input.elements.insert( self.save.to_string(), GridElement::Sel(occupied) );
+                return Ok(input);
+            },
+            CommandMode::MarkerInput => {
+                let Some(node) = scene.resolve( &self.scene_path ) else {
+                    return Err( format!("MarkerInput command '{}' found no node at '{}'!", name, self.scene_path ) );
+                };
+
+                // markers outside the grid are dropped, the rest keep their scene tree order
+                let mut points : PosList = vec![];
+                for child in node.find_children_ex( "*" ).type_( "Marker3D" ).owned( false ).done().iter_shared() {
+                    if let Ok(marker) = child.try_cast::<Node3D>() {
+                        let p = scene.to_cell( marker.get_global_position() );
+                        if in_grid( input.size, p ).is_some() {
+                            points.push( p );
+                        }
+                    }
+                }
+
+                input.elements.insert( self.save.to_string(), GridElement::List(points) );
+                return Ok(input);
+            },
+            CommandMode::PathInput => {
+                let Some(node) = scene.resolve( &self.scene_path ) else {
+                    return Err( format!("PathInput command '{}' found no node at '{}'!", name, self.scene_path ) );
+                };
+                let Ok(path) = node.try_cast::<Path3D>() else {
+                    return Err( format!("PathInput command '{}' was pointed at '{}', which isn't a Path3D!", name, self.scene_path ) );
+                };
+                let Some(curve) = path.get_curve() else {
+                    return Err( format!("PathInput command '{}' was pointed at a Path3D without a curve!", name ) );
+                };
+
+                let transform = path.get_global_transform();
+                let points : Vec<(f64, f64, f64)> = curve.get_baked_points().as_slice().iter().map( |p| scene.to_cell_space( transform * *p ) ).collect();
+                let sel = AlgorithmHelper::rasterize_polyline( &points, self.path_radius, input.size );
+
+                input.elements.insert( self.save.to_string(), GridElement::Sel(sel) );
+                return Ok(input);
+            },
+            CommandMode::VoxelizeInput => {
+                let Some(node) = scene.resolve( &self.scene_path ) else {
+                    return Err( format!("VoxelizeInput command '{}' found no node at '{}'!", name, self.scene_path ) );
+                };
+
+                let to_triangles = | faces: PackedVector3Array, transform: Transform3D | -> Vec<Triangle> {
+                    faces.as_slice().chunks_exact(3)
+                        .map( |f| [ scene.to_cell_space( transform * f[0] ), scene.to_cell_space( transform * f[1] ), scene.to_cell_space( transform * f[2] ) ] )
+                        .collect()
+                };
+                let from_triangles = | tris: Vec<Triangle> | match self.voxelize_fill {
+                    VoxelizeFill::Surface => voxelize::surface( &tris, input.size ),
+                    VoxelizeFill::Solid => voxelize::solid( &tris, input.size ),
+                };
+
+                let sel : Selection;
+                if let Ok(mesh_instance) = node.clone().try_cast::<MeshInstance3D>() {
+                    let Some(mesh) = mesh_instance.get_mesh() else {
+                        return Err( format!("VoxelizeInput command '{}' was pointed at a MeshInstance3D without a mesh!", name ) );
+                    };
+                    sel = from_triangles( to_triangles( mesh.get_faces(), mesh_instance.get_global_transform() ) );
+                } else if let Ok(collision) = node.try_cast::<CollisionShape3D>() {
+                    let Some(shape) = collision.get_shape() else {
+                        return Err( format!("VoxelizeInput command '{}' was pointed at a CollisionShape3D without a shape!", name ) );
+                    };
+                    let transform = collision.get_global_transform();
+
+                    if let Ok(concave) = shape.clone().try_cast::<ConcavePolygonShape3D>() {
+                        sel = from_triangles( to_triangles( concave.get_faces(), transform ) );
+                    } else if let Some(inside) = voxelize::primitive_inside( &shape ) {
+                        // primitives are tested at every cell center in the shape's own space
+                        let to_shape = transform.affine_inverse();
+                        let filled = voxelize::from_predicate( input.size, | c | inside( to_shape * scene.from_cell_space( c ) ) );
+                        sel = match self.voxelize_fill {
+                            VoxelizeFill::Surface => voxelize::shell( &filled ),
+                            VoxelizeFill::Solid => filled,
+                        };
+                    } else {
+                        // anything else, like a ConvexPolygonShape3D, goes through the faces of its debug mesh
+                        let faces = shape.get_debug_mesh().map( |m| m.get_faces() ).unwrap_or_default();
+                        if faces.is_empty() {
+                            return Err( format!("VoxelizeInput command '{}' can't voxelize a {}!", name, shape.get_class() ) );
+                        }
+                        sel = from_triangles( to_triangles( faces, transform ) );
+                    }
+                } else {
+                    return Err( format!("VoxelizeInput command '{}' was pointed at '{}', which isn't a MeshInstance3D or CollisionShape3D!", name, self.scene_path ) );
+                }
+
+                input.elements.insert( self.save.to_string(), GridElement::Sel(sel) );
+                return Ok(input);
+            },
             _ => { return Err( format!("Attempted to run command '{}' by providing one input, incorrectly!", name ) ); },
         }
     }